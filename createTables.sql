@@ -1,3 +1,27 @@
+-- Trust of a scan type, a body's data is never replaced by a less trustworthy scan
+create or replace function scan_type_rank(scan_type varchar) returns integer
+    language sql
+    immutable
+as
+$$
+select case scan_type
+           when 'NavBeaconDetail' then 4
+           when 'Detailed' then 3
+           when 'Basic' then 2
+           when 'AutoScan' then 1
+           else 0
+           end
+$$;
+
+-- Main star classes a fuel scoop works on (KGBFOAM), giants included ("K_OrangeGiant", "M_RedSuperGiant", ...)
+create or replace function is_scoopable(star_class varchar) returns boolean
+    language sql
+    immutable
+as
+$$
+select split_part(star_class, '_', 1) in ('K', 'G', 'B', 'F', 'O', 'A', 'M')
+$$;
+
 create table if not exists system
 (
     timestamp      bigint,
@@ -12,6 +36,9 @@ create table if not exists system
     government     varchar,
     security       varchar,
     faction        varchar,
+    all_bodies_found boolean,
+    nav_beacon_scanned boolean,
+    star_class     varchar,
 
     x              real,
     y              real,
@@ -19,11 +46,12 @@ create table if not exists system
 
     odyssey        boolean NOT NULL,
 
-    UNIQUE (name, odyssey),
     UNIQUE (address, odyssey),
     primary key (address, odyssey)
 );
 
+create index if not exists system_lower_name on system (lower(name), odyssey);
+
 create table if not exists system_faction
 (
     timestamp      bigint,
@@ -79,6 +107,49 @@ create table if not exists conflict
     primary key (system_address, faction1, faction2, odyssey)
 );
 
+create table if not exists system_traffic
+(
+    system_address bigint  NOT NULL,
+    day            bigint  NOT NULL,
+    count          integer,
+    odyssey        boolean NOT NULL,
+
+    primary key (system_address, day, odyssey),
+    foreign key (system_address, odyssey) references system (address, odyssey)
+);
+
+create table if not exists thargoid_war
+(
+    timestamp             bigint,
+    system_address        bigint,
+    current_state         varchar,
+    next_state_success    varchar,
+    next_state_failure    varchar,
+    success_state_reached boolean,
+    war_progress          real,
+    remaining_ports       integer,
+    days_remaining        integer,
+    odyssey               boolean NOT NULL,
+
+    primary key (system_address, odyssey),
+    foreign key (system_address, odyssey) references system (address, odyssey)
+);
+
+create table if not exists thargoid_war_history
+(
+    timestamp             bigint,
+    system_address        bigint,
+    current_state         varchar,
+    success_state_reached boolean,
+    war_progress          real,
+    remaining_ports       integer,
+    days_remaining        integer,
+    odyssey               boolean NOT NULL,
+
+    primary key (system_address, timestamp, odyssey),
+    foreign key (system_address, odyssey) references system (address, odyssey)
+);
+
 create table if not exists body
 (
     timestamp                bigint,
@@ -109,6 +180,8 @@ create table if not exists body
     volcanism                varchar,
     discovered               boolean,
     mapped                   boolean,
+    scan_type                varchar,
+    authoritative            boolean,
     odyssey        boolean NOT NULL,
 
 unique (id,system_address),
@@ -182,6 +255,8 @@ create table if not exists star
     surface_temperature      real,
     discovered               boolean,
     mapped                   boolean,
+    scan_type                varchar,
+    authoritative            boolean,
     odyssey                  boolean NOT NULL,
 
     primary key (system_address, id, odyssey),
@@ -198,10 +273,61 @@ create table if not exists ring
     outer_rad      real,
     mass_mt        real,
     class          varchar,
+    id             integer,
     odyssey        boolean NOT NULL,
 
-    primary key (system_address, body_id, odyssey),
-    foreign key (system_address, body_id, odyssey) references body (system_address, id, odyssey)
+    primary key (system_address, name, odyssey),
+    foreign key (system_address, odyssey) references system (address, odyssey)
+);
+
+create table if not exists ring_signal
+(
+    timestamp      bigint,
+    system_address bigint  NOT NULL,
+    ring_name      varchar NOT NULL,
+    ring_id        integer,
+    type           varchar NOT NULL,
+    count          integer,
+    odyssey        boolean NOT NULL,
+
+    primary key (system_address, ring_name, type, odyssey),
+    foreign key (system_address, odyssey) references system (address, odyssey)
+);
+
+create index if not exists ring_signal_type on ring_signal (type, odyssey);
+
+create table if not exists ring_mining
+(
+    timestamp      bigint,
+    system_address bigint  NOT NULL,
+    ring_name      varchar NOT NULL,
+    prospected     integer,
+    motherlodes    integer,
+    content_high   integer,
+    content_medium integer,
+    content_low    integer,
+    remaining_sum  real,
+    cracked        integer,
+    odyssey        boolean NOT NULL,
+
+    primary key (system_address, ring_name, odyssey),
+    foreign key (system_address, odyssey) references system (address, odyssey)
+);
+
+create table if not exists ring_mining_material
+(
+    timestamp      bigint,
+    system_address bigint  NOT NULL,
+    ring_name      varchar NOT NULL,
+    name           varchar NOT NULL,
+    samples        integer,
+    proportion_sum real,
+    proportion_max real,
+    motherlodes    integer,
+    odyssey        boolean NOT NULL,
+
+    primary key (system_address, ring_name, name, odyssey),
+    foreign key (system_address, ring_name, odyssey) references ring_mining (system_address, ring_name, odyssey)
 );
 
 
@@ -218,18 +344,197 @@ create table if not exists body_signal
     foreign key (system_address, body_id, odyssey) references body (system_address, id, odyssey)
 );
 
+create table if not exists body_genus
+(
+    timestamp      bigint,
+    system_address bigint,
+    body_id        integer,
+    genus          varchar,
+    odyssey        boolean NOT NULL,
+
+    primary key (system_address, body_id, genus, odyssey),
+    foreign key (system_address, body_id, odyssey) references body (system_address, id, odyssey)
+);
+
+create table if not exists body_organic
+(
+    timestamp      bigint,
+    system_address bigint,
+    body_id        integer,
+    genus          varchar,
+    species        varchar,
+    variant        varchar,
+    odyssey        boolean NOT NULL,
+
+    primary key (system_address, body_id, species, odyssey),
+    foreign key (system_address, body_id, odyssey) references body (system_address, id, odyssey)
+);
+
+create table if not exists codex_entry
+(
+    timestamp      bigint,
+    first_reported bigint,
+    system_address bigint,
+    body_id        integer,
+    entry_id       bigint  NOT NULL,
+    name           varchar,
+    category       varchar,
+    subcategory    varchar,
+    region         varchar,
+    latitude       real,
+    longitude      real,
+    odyssey        boolean NOT NULL,
+
+    foreign key (system_address, odyssey) references system (address, odyssey)
+);
+
+create unique index if not exists codex_entry_key on codex_entry (system_address, coalesce(body_id, -1), entry_id, odyssey);
+
+create table if not exists system_signal
+(
+    timestamp        bigint,
+    system_address   bigint,
+    name             varchar NOT NULL,
+    type             varchar,
+    is_station       boolean,
+    uss_type         varchar,
+    spawning_state   varchar,
+    spawning_faction varchar,
+    threat_level     integer,
+    expires          bigint,
+    odyssey          boolean NOT NULL,
+
+    foreign key (system_address, odyssey) references system (address, odyssey)
+);
+
+create unique index if not exists system_signal_key on system_signal (system_address, name, coalesce(uss_type, ''), coalesce(spawning_faction, ''), odyssey);
+create index if not exists system_signal_expires on system_signal (expires);
+
 create table if not exists station
+(
+    timestamp           bigint,
+    name                varchar,
+    market_id           bigint NOT NULL,
+    system_name         varchar,
+    system_address      bigint,
+    type                varchar,
+    faction             varchar,
+    government          varchar,
+    allegiance          varchar,
+    economy             varchar,
+    dist_from_star_ls   real,
+    landing_pads_small  integer,
+    landing_pads_medium integer,
+    landing_pads_large  integer,
+    body_id             integer,
+    body_name           varchar,
+    odyssey             boolean NOT NULL,
+
+    primary key (market_id, odyssey),
+    foreign key (system_address, odyssey) references system (address, odyssey)
+);
+
+create table if not exists station_service
+(
+    timestamp bigint,
+    market_id bigint,
+    service   varchar,
+    odyssey   boolean NOT NULL,
+
+    primary key (market_id, service, odyssey),
+    foreign key (market_id, odyssey) references station (market_id, odyssey)
+);
+
+create table if not exists station_economy
+(
+    timestamp  bigint,
+    market_id  bigint,
+    name       varchar,
+    proportion real,
+    odyssey    boolean NOT NULL,
+
+    primary key (market_id, name, odyssey),
+    foreign key (market_id, odyssey) references station (market_id, odyssey)
+);
+
+create table if not exists station_prohibited
+(
+    timestamp bigint,
+    market_id bigint,
+    name      varchar,
+    odyssey   boolean NOT NULL,
+
+    primary key (market_id, name, odyssey),
+    foreign key (market_id, odyssey) references station (market_id, odyssey)
+);
+
+create table if not exists settlement
+(
+    timestamp      bigint,
+    system_address bigint  NOT NULL,
+    body_id        integer NOT NULL,
+    body_name      varchar,
+    name           varchar NOT NULL,
+    market_id      bigint,
+    latitude       real,
+    longitude      real,
+    odyssey        boolean NOT NULL,
+
+    primary key (system_address, body_id, name, odyssey),
+    foreign key (system_address, odyssey) references system (address, odyssey)
+);
+
+create index if not exists settlement_market_id on settlement (market_id, odyssey);
+
+create table if not exists fleet_carrier
+(
+    timestamp                bigint,
+    market_id                bigint  NOT NULL,
+    callsign                 varchar,
+    name                     varchar,
+    docking_access           varchar,
+    allow_notorious          boolean,
+    system_address           bigint,
+    body_id                  integer,
+    jump_destination_address bigint,
+    jump_departure           bigint,
+    odyssey                  boolean NOT NULL,
+
+    primary key (market_id, odyssey)
+);
+
+create index if not exists fleet_carrier_callsign on fleet_carrier (callsign, odyssey);
+
+create table if not exists fleet_carrier_location
+(
+    timestamp      bigint,
+    market_id      bigint,
+    system_address bigint,
+    body_id        integer,
+    odyssey        boolean NOT NULL,
+
+    primary key (market_id, timestamp, odyssey),
+    foreign key (market_id, odyssey) references fleet_carrier (market_id, odyssey)
+);
+
+create table if not exists fc_material
 (
     timestamp   bigint,
-    name        varchar,
-    market_id   bigint NOT NULL,
-    system_name varchar,
+    market_id   bigint  NOT NULL,
+    material_id bigint,
+    name        varchar NOT NULL,
+    buy_price   integer,
+    sell_price  integer,
+    stock       integer,
+    demand      integer,
     odyssey     boolean NOT NULL,
 
-    primary key (market_id, odyssey),
-    foreign key (system_name, odyssey) references system (name, odyssey)
+    primary key (market_id, name, odyssey),
+    foreign key (market_id, odyssey) references fleet_carrier (market_id, odyssey)
 );
 
+create index if not exists fc_material_name on fc_material (name, odyssey);
+
 create table if not exists ship
 (
     timestamp bigint,
@@ -252,6 +557,78 @@ create table if not exists module
     foreign key (market_id, odyssey) references station (market_id, odyssey)
 );
 
+create table if not exists module_catalogue
+(
+    symbol       varchar NOT NULL,
+    category     varchar,
+    item         varchar,
+    size         integer,
+    class        integer,
+    rating       varchar,
+    mount        varchar,
+    display_name varchar,
+
+    primary key (symbol)
+);
+
+create index if not exists module_catalogue_item on module_catalogue (item, size, rating);
+
+create table if not exists ship_catalogue
+(
+    symbol       varchar NOT NULL,
+    display_name varchar,
+
+    primary key (symbol)
+);
+
+create table if not exists ship_history
+(
+    market_id  bigint,
+    ship       varchar,
+    first_seen bigint,
+    last_seen  bigint,
+    odyssey    boolean NOT NULL,
+
+    primary key (market_id, ship, odyssey),
+    foreign key (market_id, odyssey) references station (market_id, odyssey)
+);
+
+create table if not exists ship_change
+(
+    timestamp bigint,
+    market_id bigint,
+    ship      varchar,
+    change    varchar,
+    odyssey   boolean NOT NULL,
+
+    primary key (market_id, ship, timestamp, odyssey),
+    foreign key (market_id, odyssey) references station (market_id, odyssey)
+);
+
+create table if not exists module_history
+(
+    market_id  bigint,
+    name       varchar,
+    first_seen bigint,
+    last_seen  bigint,
+    odyssey    boolean NOT NULL,
+
+    primary key (market_id, name, odyssey),
+    foreign key (market_id, odyssey) references station (market_id, odyssey)
+);
+
+create table if not exists module_change
+(
+    timestamp bigint,
+    market_id bigint,
+    name      varchar,
+    change    varchar,
+    odyssey   boolean NOT NULL,
+
+    primary key (market_id, name, timestamp, odyssey),
+    foreign key (market_id, odyssey) references station (market_id, odyssey)
+);
+
 create table if not exists commodity
 (
     timestamp      bigint,
@@ -264,6 +641,7 @@ create table if not exists commodity
     demand_bracket integer,
     stock          integer,
     stock_bracket  integer,
+    status_flags   varchar[],
     odyssey        boolean NOT NULL,
 
     primary key (market_id, name, odyssey),
@@ -281,19 +659,375 @@ create table if not exists commodity_history (
     primary key (timestamp,name,odyssey)
 );
 
+create table if not exists commodity_price_history
+(
+    timestamp      bigint,
+    market_id      bigint,
+    name           varchar,
+    buy_price      integer,
+    sell_price     integer,
+    demand         integer,
+    demand_bracket integer,
+    stock          integer,
+    stock_bracket  integer,
+    odyssey        boolean NOT NULL,
+
+    primary key (market_id, name, timestamp, odyssey),
+    foreign key (market_id, odyssey) references station (market_id, odyssey)
+);
+
+create table if not exists market_commodity_candle
+(
+    market_id  bigint,
+    name       varchar,
+    resolution varchar,
+    bucket     bigint,
+    buy_open   integer,
+    buy_high   integer,
+    buy_low    integer,
+    buy_close  integer,
+    sell_open  integer,
+    sell_high  integer,
+    sell_low   integer,
+    sell_close integer,
+    timestamp  bigint,
+    odyssey    boolean NOT NULL,
+
+    primary key (market_id, name, resolution, bucket, odyssey),
+    foreign key (market_id, odyssey) references station (market_id, odyssey)
+);
+
+create index if not exists market_commodity_candle_bucket on market_commodity_candle (resolution, bucket);
+
+create table if not exists commodity_candle
+(
+    name       varchar,
+    resolution varchar,
+    bucket     bigint,
+    buy_open   integer,
+    buy_high   integer,
+    buy_low    integer,
+    buy_close  integer,
+    sell_open  integer,
+    sell_high  integer,
+    sell_low   integer,
+    sell_close integer,
+    timestamp  bigint,
+    odyssey    boolean NOT NULL,
+
+    primary key (name, resolution, bucket, odyssey)
+);
+
 create table if not exists parent
 (
     system_address bigint NOT NULL,
     body_id        integer NOT NULL,
     parent_type    varchar,
     parent_id      integer,
+    level          integer,
     odyssey        boolean NOT NULL,
 
     primary key (system_address,body_id,parent_id, odyssey),
-    foreign key (system_address,body_id, odyssey) references body(system_address,id, odyssey)
+    foreign key (system_address, odyssey) references system (address, odyssey)
+);
+
+create table if not exists barycentre
+(
+    timestamp           bigint,
+    system_address      bigint  NOT NULL,
+    body_id             integer NOT NULL,
+    semi_major_axis     real,
+    eccentricity        real,
+    orbital_inclination real,
+    periapsis           real,
+    orbital_period      real,
+    ascending_node      real,
+    mean_anomaly        real,
+    odyssey             boolean NOT NULL,
+
+    primary key (system_address, body_id, odyssey),
+    foreign key (system_address, odyssey) references system (address, odyssey)
+);
+
+create table if not exists system_exploration
+(
+    timestamp        bigint,
+    system_address   bigint,
+    body_count       integer,
+    known_bodies     integer,
+    detailed_bodies  integer,
+    all_bodies_found boolean,
+    odyssey          boolean NOT NULL,
+
+    primary key (system_address, odyssey),
+    foreign key (system_address, odyssey) references system (address, odyssey)
 );
 
 create table if not exists pid
 (
     pkey varchar primary key
-)
\ No newline at end of file
+);
+
+create or replace view partially_charted_system as
+select system.name,
+       system.address,
+       system.x,
+       system.y,
+       system.z,
+       system_exploration.body_count,
+       system_exploration.known_bodies,
+       system_exploration.detailed_bodies,
+       system_exploration.all_bodies_found,
+       system_exploration.odyssey
+from system_exploration
+         join system on system.address = system_exploration.system_address and system.odyssey = system_exploration.odyssey
+where system_exploration.body_count is null
+   or system_exploration.known_bodies < system_exploration.body_count
+   or system_exploration.detailed_bodies < system_exploration.known_bodies;
+
+create or replace view active_system_signal as
+select timestamp,
+       system_address,
+       name,
+       type,
+       is_station,
+       uss_type,
+       spawning_state,
+       spawning_faction,
+       threat_level,
+       expires,
+       odyssey
+from system_signal
+where expires is null or expires > extract(epoch from now());
+
+create or replace view fleet_carrier_details as
+select fleet_carrier.market_id,
+       fleet_carrier.callsign,
+       fleet_carrier.name,
+       fleet_carrier.docking_access,
+       fleet_carrier.allow_notorious,
+       fleet_carrier.system_address,
+       system.name as system_name,
+       fleet_carrier.body_id,
+       fleet_carrier.jump_destination_address,
+       fleet_carrier.jump_departure,
+       (select array_agg(station_service.service order by station_service.service)
+        from station_service
+        where station_service.market_id = fleet_carrier.market_id
+          and station_service.odyssey = fleet_carrier.odyssey) as services,
+       fleet_carrier.timestamp,
+       fleet_carrier.odyssey
+from fleet_carrier
+         left join system on system.address = fleet_carrier.system_address and system.odyssey = fleet_carrier.odyssey;
+
+create or replace view black_market as
+select station.market_id,
+       station.name,
+       station.system_address,
+       system.name as system_name,
+       station.dist_from_star_ls,
+       (select array_agg(station_prohibited.name order by station_prohibited.name)
+        from station_prohibited
+        where station_prohibited.market_id = station.market_id
+          and station_prohibited.odyssey = station.odyssey) as prohibited,
+       station.odyssey
+from station
+         left join system on system.address = station.system_address and system.odyssey = station.odyssey
+where exists(select 1
+             from station_service
+             where station_service.market_id = station.market_id
+               and station_service.odyssey = station.odyssey
+               and station_service.service = 'blackmarket');
+
+create or replace view module_details as
+select module.market_id,
+       module.name as symbol,
+       module_catalogue.category,
+       module_catalogue.item,
+       module_catalogue.size,
+       module_catalogue.class,
+       module_catalogue.rating,
+       module_catalogue.mount,
+       module_catalogue.display_name,
+       module.timestamp,
+       module.odyssey
+from module
+         left join module_catalogue on module_catalogue.symbol = module.name;
+
+create or replace view ship_details as
+select ship.market_id,
+       ship.ship as symbol,
+       ship_catalogue.display_name,
+       ship.timestamp,
+       ship.odyssey
+from ship
+         left join ship_catalogue on ship_catalogue.symbol = ship.ship;
+
+create or replace view settlement_details as
+select settlement.system_address,
+       system.name as system_name,
+       settlement.body_id,
+       settlement.body_name,
+       settlement.name,
+       settlement.market_id,
+       settlement.latitude,
+       settlement.longitude,
+       station.faction,
+       station.government,
+       station.allegiance,
+       station.economy,
+       (select array_agg(station_economy.name order by station_economy.proportion desc)
+        from station_economy
+        where station_economy.market_id = settlement.market_id
+          and station_economy.odyssey = settlement.odyssey) as economies,
+       (select array_agg(station_service.service order by station_service.service)
+        from station_service
+        where station_service.market_id = settlement.market_id
+          and station_service.odyssey = settlement.odyssey) as services,
+       settlement.timestamp,
+       settlement.odyssey
+from settlement
+         left join system on system.address = settlement.system_address and system.odyssey = settlement.odyssey
+         left join station on station.market_id = settlement.market_id and station.odyssey = settlement.odyssey;
+
+create or replace view ring_hotspot as
+select ring_signal.system_address,
+       system.name                                                                          as system_name,
+       system.x,
+       system.y,
+       system.z,
+       ring_signal.ring_name,
+       ring_signal.ring_id,
+       ring.body_id,
+       ring.class,
+       ring.inner_rad,
+       ring.outer_rad,
+       ring_signal.type,
+       ring_signal.count,
+       count(*) over (partition by ring_signal.system_address, ring_signal.ring_name, ring_signal.odyssey) as hotspot_types,
+       ring_signal.timestamp,
+       ring_signal.odyssey
+from ring_signal
+         left join system on system.address = ring_signal.system_address and system.odyssey = ring_signal.odyssey
+         left join ring on ring.system_address = ring_signal.system_address and ring.name = ring_signal.ring_name and ring.odyssey = ring_signal.odyssey;
+
+create or replace view ring_mining_yield as
+select ring_mining.system_address,
+       system.name                                                                     as system_name,
+       ring_mining.ring_name,
+       ring_mining_material.name                                                       as material,
+       ring_mining.prospected,
+       ring_mining.cracked,
+       ring_mining.motherlodes::real / nullif(ring_mining.prospected, 0)               as motherlode_rate,
+       ring_mining.content_high::real / nullif(ring_mining.prospected, 0)              as high_content_rate,
+       ring_mining.remaining_sum / nullif(ring_mining.prospected, 0)                   as average_remaining,
+       ring_mining_material.samples::real / nullif(ring_mining.prospected, 0)          as material_frequency,
+       ring_mining_material.proportion_sum / nullif(ring_mining_material.samples, 0)   as average_proportion,
+       ring_mining_material.proportion_max,
+       ring_mining_material.motherlodes                                                as material_motherlodes,
+       ring_mining.timestamp,
+       ring_mining.odyssey
+from ring_mining
+         join ring_mining_material on ring_mining_material.system_address = ring_mining.system_address and
+                                      ring_mining_material.ring_name = ring_mining.ring_name and
+                                      ring_mining_material.odyssey = ring_mining.odyssey
+         left join system on system.address = ring_mining.system_address and system.odyssey = ring_mining.odyssey;
+
+create or replace view body_missing_detailed_scan as
+select system_address, id, name, scan_type, odyssey
+from body
+where scan_type is null or scan_type not in ('Detailed', 'NavBeaconDetail')
+union all
+select system_address, id, name, scan_type, odyssey
+from star
+where scan_type is null or scan_type not in ('Detailed', 'NavBeaconDetail');
+
+-- Orbital tree of a system as json. Every node is a star, body or barycentre with its orbital elements,
+-- its rings/belts and the nodes orbiting it. Parents are taken from the parent table (level 0 = direct parent).
+-- select system_orbital_tree(422810995051, true);
+create or replace function system_orbital_node(p_system_address bigint, p_odyssey boolean, p_body_id integer) returns jsonb
+    language plpgsql
+    stable
+as
+$$
+declare
+    node     jsonb;
+    rings    jsonb;
+    children jsonb;
+begin
+    select jsonb_build_object('type', 'Star', 'id', id, 'name', name, 'class', type, 'subclass', subclass, 'luminosity', luminosity,
+                              'stellar_mass', stellar_mass, 'radius', radius, 'distance_from_arrival_ls', distance_from_arrival_ls,
+                              'semi_major_axis', semi_major_axis, 'eccentricity', eccentricity, 'orbital_inclination', orbital_inclination,
+                              'periapsis', periapsis, 'orbital_period', orbital_period, 'ascending_node', ascending_node, 'mean_anomaly', mean_anomaly)
+    into node
+    from star
+    where system_address = p_system_address and odyssey = p_odyssey and id = p_body_id;
+
+    if node is null then
+        select jsonb_build_object('type', 'Planet', 'id', id, 'name', name, 'class', class, 'mass_em', mass_em, 'radius', radius,
+                                  'landable', landable, 'distance_from_arrival_ls', distance_from_arrival_ls,
+                                  'semi_major_axis', semi_major_axis, 'eccentricity', eccentricity, 'orbital_inclination', orbital_inclination,
+                                  'periapsis', periapsis, 'orbital_period', orbital_period, 'ascending_node', ascending_node, 'mean_anomaly', mean_anomaly)
+        into node
+        from body
+        where system_address = p_system_address and odyssey = p_odyssey and id = p_body_id;
+    end if;
+
+    if node is null then
+        select jsonb_build_object('type', 'Null', 'id', p_body_id,
+                                  'semi_major_axis', semi_major_axis, 'eccentricity', eccentricity, 'orbital_inclination', orbital_inclination,
+                                  'periapsis', periapsis, 'orbital_period', orbital_period, 'ascending_node', ascending_node, 'mean_anomaly', mean_anomaly)
+        into node
+        from barycentre
+        where system_address = p_system_address and odyssey = p_odyssey and body_id = p_body_id;
+    end if;
+
+    if node is null then
+        -- Not scanned yet, only known as parent of another node
+        select jsonb_build_object('type', coalesce(min(parent_type), 'Null'), 'id', p_body_id)
+        into node
+        from parent
+        where system_address = p_system_address and odyssey = p_odyssey and parent_id = p_body_id;
+    end if;
+
+    select coalesce(jsonb_agg(jsonb_build_object('name', name, 'class', class, 'inner_rad', inner_rad, 'outer_rad', outer_rad, 'mass_mt', mass_mt)
+                              order by inner_rad), '[]'::jsonb)
+    into rings
+    from ring
+    where system_address = p_system_address and odyssey = p_odyssey and body_id = p_body_id;
+
+    select coalesce(jsonb_agg(system_orbital_node(p_system_address, p_odyssey, body_id) order by body_id), '[]'::jsonb)
+    into children
+    from parent
+    where system_address = p_system_address and odyssey = p_odyssey and parent_id = p_body_id and level = 0;
+
+    return node || jsonb_build_object('rings', rings, 'children', children);
+end
+$$;
+
+create or replace function system_orbital_tree(p_system_address bigint, p_odyssey boolean) returns jsonb
+    language sql
+    stable
+as
+$$
+with nodes as (select id
+               from star
+               where system_address = p_system_address and odyssey = p_odyssey
+               union
+               select id
+               from body
+               where system_address = p_system_address and odyssey = p_odyssey
+               union
+               select body_id
+               from barycentre
+               where system_address = p_system_address and odyssey = p_odyssey
+               union
+               select parent_id
+               from parent
+               where system_address = p_system_address and odyssey = p_odyssey)
+select coalesce(jsonb_agg(system_orbital_node(p_system_address, p_odyssey, nodes.id) order by nodes.id), '[]'::jsonb)
+from nodes
+where not exists (select 1
+                  from parent
+                  where parent.system_address = p_system_address and parent.odyssey = p_odyssey and parent.body_id = nodes.id and parent.level = 0);
+$$;
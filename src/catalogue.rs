@@ -0,0 +1,503 @@
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use json::JsonValue;
+use tokio::sync::Mutex;
+
+/// Module information of an outfitting symbol like "int_hyperdrive_size4_class5".
+#[derive(Clone)]
+pub struct Module {
+    /// hardpoint, utility, internal, armour or other
+    pub category: &'static str,
+    /// Symbol without prefix, size, class and mount, e.g. "hyperdrive" or "engine_fast"
+    pub item: String,
+    pub size: Option<i32>,
+    pub class: Option<i32>,
+    pub rating: Option<&'static str>,
+    pub mount: Option<&'static str>,
+    pub display_name: String,
+}
+
+const RATINGS: [&str; 5] = ["E", "D", "C", "B", "A"];
+
+const ITEM_NAMES: [(&str, &str); 85] = [
+    //internal
+    ("hyperdrive", "Frame Shift Drive"),
+    ("hyperdrive_overcharge", "Frame Shift Drive (SCO)"),
+    ("engine", "Thrusters"),
+    ("engine_fast", "Enhanced Performance Thrusters"),
+    ("powerplant", "Power Plant"),
+    ("lifesupport", "Life Support"),
+    ("powerdistributor", "Power Distributor"),
+    ("sensors", "Sensors"),
+    ("fueltank", "Fuel Tank"),
+    ("cargorack", "Cargo Rack"),
+    ("corrosionproofcargorack", "Corrosion Resistant Cargo Rack"),
+    ("shieldgenerator", "Shield Generator"),
+    ("shieldgenerator_fast", "Bi-Weave Shield Generator"),
+    ("shieldgenerator_strong", "Prismatic Shield Generator"),
+    ("fuelscoop", "Fuel Scoop"),
+    ("refinery", "Refinery"),
+    ("repairer", "Auto Field-Maintenance Unit"),
+    ("detailedsurfacescanner", "Detailed Surface Scanner"),
+    ("dockingcomputer_standard", "Standard Docking Computer"),
+    ("dockingcomputer_advanced", "Advanced Docking Computer"),
+    ("supercruiseassist", "Supercruise Assist"),
+    ("hullreinforcement", "Hull Reinforcement Package"),
+    ("metaalloyhullreinforcement", "Meta Alloy Hull Reinforcement"),
+    ("guardianhullreinforcement", "Guardian Hull Reinforcement"),
+    ("modulereinforcement", "Module Reinforcement Package"),
+    ("guardianmodulereinforcement", "Guardian Module Reinforcement"),
+    ("guardianshieldreinforcement", "Guardian Shield Reinforcement"),
+    ("guardianfsdbooster", "Guardian FSD Booster"),
+    ("shieldcellbank", "Shield Cell Bank"),
+    ("passengercabin", "Passenger Cabin"),
+    ("fighterbay", "Fighter Hangar"),
+    ("buggybay", "Planetary Vehicle Hangar"),
+    ("fsdinterdictor", "Frame Shift Drive Interdictor"),
+    ("dronecontrol_collection", "Collector Limpet Controller"),
+    ("dronecontrol_prospector", "Prospector Limpet Controller"),
+    ("dronecontrol_fueltransfer", "Fuel Transfer Limpet Controller"),
+    ("dronecontrol_repair", "Repair Limpet Controller"),
+    ("dronecontrol_resourcesiphon", "Hatch Breaker Limpet Controller"),
+    ("dronecontrol_recon", "Recon Limpet Controller"),
+    ("dronecontrol_decontamination", "Decontamination Limpet Controller"),
+    ("dronecontrol_unkvesselresearch", "Research Limpet Controller"),
+    ("multidronecontrol_mining", "Mining Multi Limpet Controller"),
+    ("multidronecontrol_operations", "Operations Multi Limpet Controller"),
+    ("multidronecontrol_rescue", "Rescue Multi Limpet Controller"),
+    ("multidronecontrol_xeno", "Xeno Multi Limpet Controller"),
+    ("multidronecontrol_universal", "Universal Multi Limpet Controller"),
+    ("planetapproachsuite", "Planetary Approach Suite"),
+    ("planetapproachsuite_advanced", "Advanced Planetary Approach Suite"),
+    //hardpoints
+    ("pulselaser", "Pulse Laser"),
+    ("beamlaser", "Beam Laser"),
+    ("pulselaserburst", "Burst Laser"),
+    ("multicannon", "Multi-Cannon"),
+    ("cannon", "Cannon"),
+    ("slugshot", "Fragment Cannon"),
+    ("railgun", "Rail Gun"),
+    ("plasmaaccelerator", "Plasma Accelerator"),
+    ("basicmissilerack", "Seeker Missile Rack"),
+    ("dumbfiremissilerack", "Missile Rack"),
+    ("minelauncher", "Mine Launcher"),
+    ("advancedtorppylon", "Torpedo Pylon"),
+    ("mininglaser", "Mining Laser"),
+    ("mining_abrblstr", "Abrasion Blaster"),
+    ("mining_seismchrgwarhd", "Seismic Charge Launcher"),
+    ("mining_subsurfdispmisle", "Sub-Surface Displacement Missile"),
+    ("atdumbfiremissile", "AX Missile Rack"),
+    ("atmulticannon", "AX Multi-Cannon"),
+    ("causticmissile", "Enzyme Missile Rack"),
+    ("flakmortar", "Remote Release Flak Launcher"),
+    ("flechettelauncher", "Remote Release Flechette Launcher"),
+    ("plasmashockcannon", "Shock Cannon"),
+    ("guardian_gausscannon", "Guardian Gauss Cannon"),
+    ("guardian_plasmalauncher", "Guardian Plasma Charger"),
+    ("guardian_shardcannon", "Guardian Shard Cannon"),
+    //utilities
+    ("shieldbooster", "Shield Booster"),
+    ("heatsinklauncher", "Heat Sink Launcher"),
+    ("chafflauncher", "Chaff Launcher"),
+    ("plasmapointdefence", "Point Defence"),
+    ("electroniccountermeasure", "Electronic Countermeasure"),
+    ("cargoscanner", "Manifest Scanner"),
+    ("cloudscanner", "Frame Shift Wake Scanner"),
+    ("crimescanner", "Kill Warrant Scanner"),
+    ("mrascanner", "Pulse Wave Analyser"),
+    ("antiunknownshutdown", "Shutdown Field Neutraliser"),
+    ("xenoscanner", "Xeno Scanner"),
+    ("causticsinklauncher", "Caustic Sink Launcher"),
+];
+
+const ARMOUR_GRADES: [(&str, &str); 5] = [
+    ("grade1", "Lightweight Alloy"),
+    ("grade2", "Reinforced Alloy"),
+    ("grade3", "Military Grade Composite"),
+    ("mirrored", "Mirrored Surface Composite"),
+    ("reactive", "Reactive Surface Composite"),
+];
+
+/// Reference outfitting and shipyard symbols in the layout of the EDCD FDevIDs csv files
+/// (https://github.com/EDCD/FDevIDs). Symbols missing here are parsed by [parse_module].
+const OUTFITTING: &str = include_str!("../data/outfitting.csv");
+const SHIPYARD: &str = include_str!("../data/shipyard.csv");
+
+/// Outfitting and shipyard symbols come in mixed case ("Hpt_PulseLaser_Fixed_Small", "CobraMkIII").
+/// They are stored lowercase so they can be joined against the catalogue.
+pub fn normalize_symbol(symbol: &JsonValue) -> String {
+    symbol.to_string().to_lowercase()
+}
+
+/// Splits a csv file into one column name -> value map per row.
+/// The reference files don't quote values, so a plain split is enough.
+fn csv_rows(csv: &'static str) -> Vec<HashMap<&'static str, &'static str>> {
+    let mut lines = csv.lines();
+    let header: Vec<&str> = lines.next().unwrap_or_default().split(',').collect();
+    lines.filter(|line| !line.trim().is_empty())
+        .map(|line| header.iter().copied().zip(line.split(',').map(str::trim)).collect())
+        .collect()
+}
+
+fn reference_ships() -> &'static HashMap<String, &'static str> {
+    static SHIPS: OnceLock<HashMap<String, &'static str>> = OnceLock::new();
+    SHIPS.get_or_init(|| {
+        csv_rows(SHIPYARD).into_iter()
+            .map(|row| (row["symbol"].to_lowercase(), row["name"]))
+            .collect()
+    })
+}
+
+fn reference_modules() -> &'static HashMap<String, Module> {
+    static MODULES: OnceLock<HashMap<String, Module>> = OnceLock::new();
+    MODULES.get_or_init(|| {
+        csv_rows(OUTFITTING).into_iter()
+            .map(|row| {
+                let symbol = row["symbol"].to_lowercase();
+                let module = reference_module(&symbol, &row);
+                (symbol, module)
+            })
+            .collect()
+    })
+}
+
+/// Builds a module from a row of the reference outfitting csv.
+/// The item is still taken from the symbol, so reference and parsed modules can be searched alike.
+fn reference_module(symbol: &str, row: &HashMap<&'static str, &'static str>) -> Module {
+    let parsed = parse_module(symbol);
+    let armour = !row["ship"].is_empty();
+    let category = match row["category"] {
+        "hardpoint" => "hardpoint",
+        "utility" => "utility",
+        _ if armour => "armour",
+        _ => "internal",
+    };
+    let size = if armour { None } else { row["class"].parse::<i32>().ok() };
+    let rating = Some(row["rating"]).filter(|rating| !rating.is_empty());
+    let class = rating
+        .and_then(|rating| RATINGS.iter().position(|known| *known == rating))
+        .map(|position| position as i32 + 1)
+        .or(parsed.class);
+    //Utility rows have no mount column, their symbols still carry one
+    let mount = match row["mount"] {
+        "Fixed" => Some("fixed"),
+        "Gimballed" => Some("gimbal"),
+        "Turreted" => Some("turret"),
+        _ => parsed.mount,
+    };
+    let display_name = match (armour, size, rating) {
+        (true, _, _) => format!("{} {}", row["ship"], row["name"]),
+        (false, Some(size), Some(rating)) if !row["mount"].is_empty() => format!("{}{} {} ({})", size, rating, row["name"], row["mount"]),
+        (false, Some(size), Some(rating)) => format!("{}{} {}", size, rating, row["name"]),
+        _ => row["name"].to_string(),
+    };
+
+    Module {
+        category,
+        item: parsed.item,
+        size,
+        class,
+        rating,
+        mount,
+        display_name,
+    }
+}
+
+/// Catalogue entry of a module, from the reference table or parsed from the symbol if it is unknown.
+pub fn module(symbol: &str) -> Module {
+    reference_modules().get(symbol).cloned().unwrap_or_else(|| parse_module(symbol))
+}
+
+pub fn ship_name(symbol: &str) -> String {
+    reference_ships().get(symbol)
+        .map(|name| name.to_string())
+        .unwrap_or(symbol.to_string())
+}
+
+fn item_name(item: &str) -> String {
+    ITEM_NAMES.iter()
+        .find(|(symbol, _)| *symbol == item)
+        .map(|(_, name)| name.to_string())
+        .unwrap_or(item.replace('_', " "))
+}
+
+/// Parses a normalized outfitting symbol, the fallback for symbols missing in the reference table.
+/// Internals and utilities: "int_hyperdrive_size4_class5" -> size 4, class 5, rating A, "4A Frame Shift Drive"
+/// Hardpoints: "hpt_pulselaser_fixed_small" -> size 1, mount fixed, "Pulse Laser (Fixed, Small)"
+/// Armour: "anaconda_armour_grade3" -> "Anaconda Military Grade Composite"
+pub fn parse_module(symbol: &str) -> Module {
+    if let Some((ship, grade)) = symbol.split_once("_armour_") {
+        let grade_name = ARMOUR_GRADES.iter()
+            .find(|(armour_grade, _)| *armour_grade == grade)
+            .map(|(_, name)| name.to_string())
+            .unwrap_or(grade.to_string());
+        return Module {
+            category: "armour",
+            item: "armour".to_string(),
+            size: None,
+            class: None,
+            rating: None,
+            mount: None,
+            display_name: format!("{} {}", ship_name(ship), grade_name),
+        };
+    }
+
+    let internal = symbol.starts_with("int_");
+    if !internal && !symbol.starts_with("hpt_") {
+        return Module {
+            category: "other",
+            item: symbol.to_string(),
+            size: None,
+            class: None,
+            rating: None,
+            mount: None,
+            display_name: symbol.to_string(),
+        };
+    }
+
+    let mut item_parts = Vec::new();
+    let mut size = None;
+    let mut class = None;
+    let mut mount = None;
+    let mut weapon_size = None;
+    for part in symbol[4..].split('_') {
+        if let Some(number) = part.strip_prefix("size").and_then(|number| number.parse::<i32>().ok()) {
+            size = Some(number);
+        } else if let Some(number) = part.strip_prefix("class").and_then(|number| number.parse::<i32>().ok()) {
+            class = Some(number);
+        } else if let Some(fitting) = ["fixed", "gimbal", "turret"].into_iter().find(|fitting| *fitting == part) {
+            mount = Some(fitting);
+        } else if let Some(hardpoint_size) = ["tiny", "small", "medium", "large", "huge"].into_iter().position(|hardpoint_size| hardpoint_size == part) {
+            weapon_size = Some(hardpoint_size as i32);
+        } else {
+            item_parts.push(part);
+        }
+    }
+    let item = item_parts.join("_");
+    let size = size.or(weapon_size);
+    let rating = class.filter(|class| (1..=5).contains(class)).map(|class| RATINGS[class as usize - 1]);
+    let category = if internal {
+        "internal"
+    } else if size == Some(0) {
+        "utility"
+    } else {
+        "hardpoint"
+    };
+
+    let display_name = match (category, size, rating, mount) {
+        ("hardpoint", _, _, Some(mount)) => {
+            let size_name = ["Tiny", "Small", "Medium", "Large", "Huge"][weapon_size.unwrap_or(0) as usize];
+            let mount_name = match mount {
+                "fixed" => "Fixed",
+                "gimbal" => "Gimballed",
+                _ => "Turreted",
+            };
+            format!("{} ({}, {})", item_name(&item), mount_name, size_name)
+        }
+        (_, Some(size), Some(rating), _) => format!("{}{} {}", size, rating, item_name(&item)),
+        _ => item_name(&item),
+    };
+
+    Module {
+        category,
+        item,
+        size,
+        class,
+        rating,
+        mount,
+        display_name,
+    }
+}
+
+/// Adds modules to module_catalogue that aren't known yet.
+pub async fn insert_modules(client: &Arc<Mutex<tokio_postgres::Client>>, symbols: &[String]) {
+    //language=postgresql
+    let insert = "
+        INSERT INTO module_catalogue (symbol, category, item, size, class, rating, mount, display_name)
+        SELECT * FROM unnest($1::varchar[], $2::varchar[], $3::varchar[], $4::integer[], $5::integer[], $6::varchar[], $7::varchar[], $8::varchar[])
+        ON CONFLICT (symbol) DO NOTHING;";
+    store_modules(client, insert, symbols).await;
+}
+
+async fn store_modules(client: &Arc<Mutex<tokio_postgres::Client>>, sql: &str, symbols: &[String]) {
+    let modules: Vec<Module> = symbols.iter().map(|symbol| module(symbol)).collect();
+    let categories: Vec<&str> = modules.iter().map(|module| module.category).collect();
+    let items: Vec<&str> = modules.iter().map(|module| module.item.as_str()).collect();
+    let sizes: Vec<Option<i32>> = modules.iter().map(|module| module.size).collect();
+    let classes: Vec<Option<i32>> = modules.iter().map(|module| module.class).collect();
+    let ratings: Vec<Option<&str>> = modules.iter().map(|module| module.rating).collect();
+    let mounts: Vec<Option<&str>> = modules.iter().map(|module| module.mount).collect();
+    let display_names: Vec<&str> = modules.iter().map(|module| module.display_name.as_str()).collect();
+
+    client.lock().await.execute(sql, &[&symbols, &categories, &items, &sizes, &classes, &ratings, &mounts, &display_names]).await.unwrap();
+}
+
+/// Adds ships to ship_catalogue that aren't known yet.
+pub async fn insert_ships(client: &Arc<Mutex<tokio_postgres::Client>>, symbols: &[String]) {
+    let display_names: Vec<String> = symbols.iter().map(|symbol| ship_name(symbol)).collect();
+
+    //language=postgresql
+    let insert = "
+        INSERT INTO ship_catalogue (symbol, display_name)
+        SELECT * FROM unnest($1::varchar[], $2::varchar[])
+        ON CONFLICT (symbol) DO NOTHING;";
+    client.lock().await.execute(insert, &[&symbols, &display_names]).await.unwrap();
+}
+
+/// Writes the reference modules and ships, replacing entries that were parsed from their symbol before.
+pub async fn insert_reference(client: &Arc<Mutex<tokio_postgres::Client>>) {
+    let modules: Vec<String> = reference_modules().keys().cloned().collect();
+    //language=postgresql
+    let upsert = "
+        INSERT INTO module_catalogue (symbol, category, item, size, class, rating, mount, display_name)
+        SELECT * FROM unnest($1::varchar[], $2::varchar[], $3::varchar[], $4::integer[], $5::integer[], $6::varchar[], $7::varchar[], $8::varchar[])
+        ON CONFLICT (symbol) DO UPDATE SET
+            category = excluded.category,
+            item = excluded.item,
+            size = excluded.size,
+            class = excluded.class,
+            rating = excluded.rating,
+            mount = excluded.mount,
+            display_name = excluded.display_name;";
+    store_modules(client, upsert, &modules).await;
+
+    let ships: Vec<String> = reference_ships().keys().cloned().collect();
+    let display_names: Vec<&str> = ships.iter().map(|ship| reference_ships()[ship]).collect();
+    //language=postgresql
+    let upsert = "
+        INSERT INTO ship_catalogue (symbol, display_name)
+        SELECT * FROM unnest($1::varchar[], $2::varchar[])
+        ON CONFLICT (symbol) DO UPDATE SET display_name = excluded.display_name;";
+    client.lock().await.execute(upsert, &[&ships, &display_names]).await.unwrap();
+}
+
+/// Adds catalogue entries for modules and ships stored before the catalogue existed.
+pub async fn insert_missing(client: &Arc<Mutex<tokio_postgres::Client>>) {
+    //language=postgresql
+    let select = "SELECT DISTINCT name FROM module WHERE NOT EXISTS (SELECT 1 FROM module_catalogue WHERE symbol = module.name);";
+    let rows = client.lock().await.query(select, &[]).await.unwrap();
+    let modules: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+    insert_modules(client, &modules).await;
+
+    //language=postgresql
+    let select = "SELECT DISTINCT ship FROM ship WHERE NOT EXISTS (SELECT 1 FROM ship_catalogue WHERE symbol = ship.ship);";
+    let rows = client.lock().await.query(select, &[]).await.unwrap();
+    let ships: Vec<String> = rows.iter().map(|row| row.get(0)).collect();
+    insert_ships(client, &ships).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_internal_module() {
+        let module = parse_module("int_hyperdrive_size4_class5");
+        assert_eq!(module.category, "internal");
+        assert_eq!(module.item, "hyperdrive");
+        assert_eq!(module.size, Some(4));
+        assert_eq!(module.class, Some(5));
+        assert_eq!(module.rating, Some("A"));
+        assert_eq!(module.mount, None);
+        assert_eq!(module.display_name, "4A Frame Shift Drive");
+    }
+
+    #[test]
+    fn parses_hardpoint() {
+        let module = parse_module("hpt_pulselaser_fixed_small");
+        assert_eq!(module.category, "hardpoint");
+        assert_eq!(module.item, "pulselaser");
+        assert_eq!(module.size, Some(1));
+        assert_eq!(module.rating, None);
+        assert_eq!(module.mount, Some("fixed"));
+        assert_eq!(module.display_name, "Pulse Laser (Fixed, Small)");
+    }
+
+    #[test]
+    fn parses_utility() {
+        let module = parse_module("hpt_shieldbooster_size0_class5");
+        assert_eq!(module.category, "utility");
+        assert_eq!(module.item, "shieldbooster");
+        assert_eq!(module.size, Some(0));
+        assert_eq!(module.rating, Some("A"));
+        assert_eq!(module.display_name, "0A Shield Booster");
+
+        //Tiny hardpoints are utility mounts
+        let module = parse_module("hpt_heatsinklauncher_turret_tiny");
+        assert_eq!(module.category, "utility");
+        assert_eq!(module.item, "heatsinklauncher");
+        assert_eq!(module.size, Some(0));
+        assert_eq!(module.mount, Some("turret"));
+        assert_eq!(module.display_name, "Heat Sink Launcher");
+    }
+
+    #[test]
+    fn parses_armour() {
+        let module = parse_module("anaconda_armour_grade3");
+        assert_eq!(module.category, "armour");
+        assert_eq!(module.size, None);
+        assert_eq!(module.display_name, "Anaconda Military Grade Composite");
+    }
+
+    #[test]
+    fn looks_up_reference_modules() {
+        let module = super::module("int_planetapproachsuite");
+        assert_eq!(module.category, "internal");
+        assert_eq!(module.item, "planetapproachsuite");
+        assert_eq!(module.rating, Some("I"));
+        assert_eq!(module.display_name, "1I Planetary Approach Suite");
+
+        let module = super::module("hpt_atdumbfiremissile_fixed_large");
+        assert_eq!(module.category, "hardpoint");
+        assert_eq!(module.item, "atdumbfiremissile");
+        assert_eq!(module.size, Some(3));
+        assert_eq!(module.class, Some(5));
+        assert_eq!(module.rating, Some("A"));
+        assert_eq!(module.mount, Some("fixed"));
+        assert_eq!(module.display_name, "3A AX Missile Rack (Fixed)");
+
+        let module = super::module("hpt_pulselaser_fixed_small");
+        assert_eq!(module.rating, Some("F"));
+        assert_eq!(module.class, None);
+        assert_eq!(module.display_name, "1F Pulse Laser (Fixed)");
+
+        let module = super::module("hpt_causticmissile_fixed_medium");
+        assert_eq!(module.item, "causticmissile");
+        assert_eq!(module.display_name, "2B Enzyme Missile Rack (Fixed)");
+
+        let module = super::module("hpt_guardian_gausscannon_fixed_small");
+        assert_eq!(module.item, "guardian_gausscannon");
+        assert_eq!(module.display_name, "1D Guardian Gauss Cannon (Fixed)");
+
+        let module = super::module("hpt_heatsinklauncher_turret_tiny");
+        assert_eq!(module.category, "utility");
+        assert_eq!(module.mount, Some("turret"));
+        assert_eq!(module.display_name, "0I Heat Sink Launcher");
+
+        let module = super::module("anaconda_armour_grade3");
+        assert_eq!(module.category, "armour");
+        assert_eq!(module.size, None);
+        assert_eq!(module.display_name, "Anaconda Military Grade Composite");
+    }
+
+    #[test]
+    fn falls_back_to_parser() {
+        let module = super::module("hpt_pulselaser_fixed_colossal");
+        assert_eq!(module.category, "hardpoint");
+        assert_eq!(module.item, "pulselaser_colossal");
+        assert_eq!(module.mount, Some("fixed"));
+    }
+
+    #[test]
+    fn looks_up_reference_ships() {
+        assert_eq!(ship_name("cobramkiii"), "Cobra Mk III");
+        assert_eq!(ship_name("unknownship"), "unknownship");
+    }
+
+    #[test]
+    fn normalizes_symbol() {
+        assert_eq!(normalize_symbol(&JsonValue::from("Hpt_PulseLaser_Fixed_Small")), "hpt_pulselaser_fixed_small");
+        assert_eq!(normalize_symbol(&JsonValue::from("CobraMkIII")), "cobramkiii");
+    }
+}
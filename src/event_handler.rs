@@ -6,6 +6,15 @@ use json::JsonValue;
 use log::warn;
 use tokio::sync::Mutex;
 
+use crate::catalogue;
+
+/// Seconds a USS signal is kept if EDDN stripped its TimeRemaining
+const USS_SIGNAL_TTL: i64 = 60 * 60;
+/// Seconds a fleet carrier signal is kept, carriers can jump away at any time
+const CARRIER_SIGNAL_TTL: i64 = 24 * 60 * 60;
+/// Seconds other station signals are kept, megaships move weekly
+const STATION_SIGNAL_TTL: i64 = 7 * 24 * 60 * 60;
+
 pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Client>>) {
     if client.lock().await.is_closed(){
         process::exit(20);
@@ -170,19 +179,191 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
 
                     //TODO  faction_active_state, faction_recovering_state, conflicts
                 }
+
+                //"ThargoidWar":{ "CurrentState":"Thargoid_Controlled", "NextStateSuccess":"Thargoid_Recovery", "NextStateFailure":"Thargoid_Controlled",
+                // "SuccessStateReached":false, "WarProgress":0.365285, "RemainingPorts":0, "EstimatedRemainingTime":"3 Days" }
+                let thargoid_war = &message["ThargoidWar"];
+                if thargoid_war.is_null() {
+                    //No war in this system (anymore)
+                    //language=postgresql
+                    let delete = "DELETE FROM thargoid_war WHERE system_address = $1 and odyssey = $2;";
+                    client.lock().await.execute(delete, &[&address, &odyssey]).await.unwrap();
+                } else {
+                    let current_state = thargoid_war["CurrentState"].as_str();
+                    let next_state_success = thargoid_war["NextStateSuccess"].as_str();
+                    let next_state_failure = thargoid_war["NextStateFailure"].as_str();
+                    let success_state_reached = thargoid_war["SuccessStateReached"].as_bool();
+                    let war_progress = thargoid_war["WarProgress"].as_f32();
+                    let remaining_ports = thargoid_war["RemainingPorts"].as_i32();
+                    let days_remaining = thargoid_war["EstimatedRemainingTime"].as_str().and_then(parse_days);
+
+                    //Snapshot only if something changed since the last report
+                    //language=postgresql
+                    let history = "
+                        INSERT INTO thargoid_war_history
+                            (timestamp, system_address, current_state, success_state_reached, war_progress, remaining_ports, days_remaining, odyssey)
+                        SELECT $1::bigint, $2::bigint, $3::varchar, $4::boolean, $5::real, $6::integer, $7::integer, $8::boolean
+                        WHERE NOT EXISTS (
+                            SELECT 1 FROM thargoid_war WHERE system_address = $2 AND odyssey = $8
+                                AND current_state IS NOT DISTINCT FROM $3
+                                AND success_state_reached IS NOT DISTINCT FROM $4
+                                AND war_progress IS NOT DISTINCT FROM $5
+                                AND remaining_ports IS NOT DISTINCT FROM $6
+                                AND days_remaining IS NOT DISTINCT FROM $7
+                        )
+                        ON CONFLICT (system_address, timestamp, odyssey) DO NOTHING;";
+                    client.lock().await.execute(history,
+                                                &[&timestamp, &address, &current_state, &success_state_reached, &war_progress, &remaining_ports, &days_remaining, &odyssey],
+                    ).await.unwrap();
+
+                    //language=postgresql
+                    let insert = "
+                        INSERT INTO thargoid_war
+                            (timestamp, system_address, current_state, next_state_success, next_state_failure, success_state_reached, war_progress, remaining_ports, days_remaining, odyssey)
+                        VALUES
+                            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                        ON CONFLICT (system_address, odyssey) DO UPDATE SET
+                            timestamp = excluded.timestamp,
+                            current_state = excluded.current_state,
+                            next_state_success = excluded.next_state_success,
+                            next_state_failure = excluded.next_state_failure,
+                            success_state_reached = excluded.success_state_reached,
+                            war_progress = excluded.war_progress,
+                            remaining_ports = excluded.remaining_ports,
+                            days_remaining = excluded.days_remaining;";
+                    client.lock().await.execute(insert,
+                                                &[&timestamp, &address, &current_state, &next_state_success, &next_state_failure, &success_state_reached, &war_progress, &remaining_ports, &days_remaining, &odyssey],
+                    ).await.unwrap();
+                }
+
+                //Location and CarrierJump while docked carry the station details as well
+                if message["Docked"].as_bool() == Some(true) {
+                    if let Some(station_name) = message["StationName"].as_str() {
+                        upsert_station_details(&client, timestamp, &message, station_name, odyssey).await;
+                    }
+                }
+
+                //Commander is aboard a fleet carrier (CarrierJump or Location while docked on one)
+                if message["StationType"].as_str() == Some("FleetCarrier") {
+                    if let Some(market_id) = message["MarketID"].as_i64() {
+                        upsert_fleet_carrier(&client, timestamp, market_id, message["StationName"].as_str(), None, None, None, odyssey).await;
+                        record_fleet_carrier_location(&client, timestamp, market_id, address, message["BodyID"].as_i32(), odyssey).await;
+                    }
+                }
+
+                //Arrived at the main star, its class is known if the star has been scanned already
+                if message["BodyType"].as_str() == Some("Star") {
+                    if let Some(body_id) = message["BodyID"].as_i32() {
+                        //language=postgresql
+                        let update = "UPDATE system SET star_class = star.type FROM star WHERE system.address = $1 AND system.odyssey = $3 AND star.system_address = $1 AND star.id = $2 AND star.odyssey = $3;";
+                        client.lock().await.execute(update, &[&address, &body_id, &odyssey]).await.unwrap();
+                    }
+                }
             }
         }
         "SupercruiseEntry" => {}
         "SupercruiseExit" => {}
         //{ "timestamp":"2022-10-16T23:25:05Z", "event":"StartJump", "JumpType":"Hyperspace", "StarSystem":"Ogmar", "SystemAddress":84180519395914, "StarClass":"K" }
-        "StartJump" => {} //If jump has been initialised
+        "StartJump" => {
+            //If jump has been initialised
+            //Supercruise jumps have no target system
+            if let (Some(system_address), Some(star_class)) = (message["SystemAddress"].as_i64(), message["StarClass"].as_str()) {
+                update_star_class(&client, system_address, star_class, odyssey).await;
+            }
+        }
         //{ "timestamp":"2022-10-16T23:24:46Z", "event":"FSDTarget", "Name":"Ogmar", "SystemAddress":84180519395914, "StarClass":"K", "RemainingJumpsInRoute":1 }
-        "FSDTarget" => {} //If system has been targeted
-        "NavRoute" => {} //If route has been set -> check json for further information
+        "FSDTarget" => {
+            //If system has been targeted
+            if let (Some(system_address), Some(star_class)) = (message["SystemAddress"].as_i64(), message["StarClass"].as_str()) {
+                update_star_class(&client, system_address, star_class, odyssey).await;
+            }
+        }
+        "NavRoute" => {
+            //If route has been set -> check json for further information
+            //{ "timestamp":"2023-09-05T13:40:12Z", "event":"NavRoute", "Route":[
+            // { "StarSystem":"Ogmar", "SystemAddress":84180519395914, "StarPos":[-9534.00000,-905.28125,19802.03125], "StarClass":"K" },
+            // { "StarSystem":"Colonia", "SystemAddress":3238296097059, "StarPos":[-9530.50000,-910.28125,19808.12500], "StarClass":"F" } ] }
+            let route = &message["Route"];
+            let names: Vec<&str> = route.members().map(|system| system["StarSystem"].as_str().unwrap()).collect();
+            let addresses: Vec<i64> = route.members().map(|system| system["SystemAddress"].as_i64().unwrap()).collect();
+            let xs: Vec<Option<f32>> = route.members().map(|system| system["StarPos"][0].as_f32()).collect();
+            let ys: Vec<Option<f32>> = route.members().map(|system| system["StarPos"][1].as_f32()).collect();
+            let zs: Vec<Option<f32>> = route.members().map(|system| system["StarPos"][2].as_f32()).collect();
+            let star_classes: Vec<Option<&str>> = route.members().map(|system| system["StarClass"].as_str()).collect();
+
+            //Systems never jumped to are known by name and position at least
+            //language=postgresql
+            let insert = "
+                INSERT INTO system (timestamp, name, address, x, y, z, odyssey)
+                SELECT $1, route.name, route.address, route.x, route.y, route.z, $2
+                FROM unnest($3::varchar[], $4::bigint[], $5::real[], $6::real[], $7::real[]) AS route(name, address, x, y, z)
+                ON CONFLICT DO NOTHING;";
+            client.lock().await.execute(insert, &[&timestamp, &odyssey, &names, &addresses, &xs, &ys, &zs]).await.unwrap();
+
+            //language=postgresql
+            let update = "
+                UPDATE system SET star_class = route.star_class
+                FROM unnest($1::bigint[], $2::varchar[]) AS route(address, star_class)
+                WHERE system.address = route.address AND system.odyssey = $3 AND route.star_class IS NOT NULL;";
+            client.lock().await.execute(update, &[&addresses, &star_classes, &odyssey]).await.unwrap();
+
+            //Only counts per system and day are kept, nothing about who plotted the route
+            //language=postgresql
+            let traffic = "
+                INSERT INTO system_traffic (system_address, day, count, odyssey)
+                SELECT DISTINCT route.address, $1::bigint - $1::bigint % 86400, 1, $2::boolean
+                FROM unnest($3::bigint[]) AS route(address)
+                ON CONFLICT (system_address, day, odyssey) DO UPDATE SET count = system_traffic.count + 1;";
+            match client.lock().await.execute(traffic, &[&timestamp, &odyssey, &addresses]).await {
+                Ok(_) => {}
+                Err(err) => {
+                    if !err.to_string().contains("violates foreign key constraint") {
+                        panic!("{}", err);
+                    }
+                }
+            }
+        }
         "NavRouteClear" => {} //If navigation is complete -> no further information
 
         //Approaching
-        "ApproachSettlement" => {}
+        "ApproachSettlement" => {
+            //{ "timestamp":"2023-09-05T13:21:44Z", "event":"ApproachSettlement", "Name":"Hilbert's Enterprise", "MarketID":3869836544, "StationFaction":{ "Name":"Jaques" },
+            // "StationGovernment":"$government_Cooperative;", "StationAllegiance":"Independent", "StationServices":[ "dock", "autodock", "commodities", "contacts", "missions", "refuel", "repair", "facilitator" ],
+            // "StationEconomy":"$economy_Extraction;", "StationEconomies":[ { "Name":"$economy_Extraction;", "Proportion":1.000000 } ], "SystemAddress":3238296097059,
+            // "StarSystem":"Colonia", "StarPos":[-9530.50000,-910.28125,19808.12500], "BodyID":18, "BodyName":"Colonia 2 c", "Latitude":-24.120611, "Longitude":101.254250 }
+            let system_address = message["SystemAddress"].as_i64().unwrap();
+            let body_id = message["BodyID"].as_i32().unwrap();
+            let name = message["Name"].as_str().unwrap();
+            let market_id = message["MarketID"].as_i64();
+
+            if let Some(system_name) = message["StarSystem"].as_str() {
+                insert_system_if_missing(&client, timestamp, system_name, system_address, &message["StarPos"], odyssey).await;
+            }
+            //Settlements with a market are stations as well
+            upsert_station_details(&client, timestamp, &message, name, odyssey).await;
+
+            //language=postgresql
+            let sql = "
+                INSERT INTO settlement (timestamp, system_address, body_id, body_name, name, market_id, latitude, longitude, odyssey)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+                ON CONFLICT (system_address, body_id, name, odyssey) DO UPDATE SET
+                    timestamp = excluded.timestamp,
+                    body_name = coalesce(excluded.body_name, settlement.body_name),
+                    market_id = coalesce(excluded.market_id, settlement.market_id),
+                    latitude = coalesce(excluded.latitude, settlement.latitude),
+                    longitude = coalesce(excluded.longitude, settlement.longitude);";
+            match client.lock().await.execute(sql, &[
+                &timestamp, &system_address, &body_id, &message["BodyName"].as_str(), &name, &market_id,
+                &message["Latitude"].as_f32(), &message["Longitude"].as_f32(), &odyssey
+            ]).await {
+                Ok(_) => {}
+                Err(err) => {
+                    if !err.to_string().contains("violates foreign key constraint") {
+                        panic!("{}", err);
+                    }
+                }
+            }
+        }
         "ApproachBody" => {}
         "LeaveBody" => {}
         "Liftoff" => {}
@@ -192,9 +373,34 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
 
         //Scanning
         "DiscoveryScan" => {}
-        "FSSAllBodiesFound" => {}
+        "FSSAllBodiesFound" => {
+            //{ "timestamp":"2023-09-05T12:20:11Z", "event":"FSSAllBodiesFound", "SystemName":"Byaa Broae IS-B d13-0", "SystemAddress":11148500979, "Count":9 }
+            let system_address = message["SystemAddress"].as_i64().unwrap();
+            let count = message["Count"].as_i32();
+
+            insert_system_if_missing(&client, timestamp, message["SystemName"].as_str().unwrap(), system_address, &message["StarPos"], odyssey).await;
+
+            //language=postgresql
+            let update = "UPDATE system SET all_bodies_found = true, body_count = coalesce($1, body_count) WHERE address = $2 and odyssey = $3;";
+            client.lock().await.execute(update, &[&count, &system_address, &odyssey]).await.unwrap();
+
+            refresh_system_exploration(&client, timestamp, system_address, odyssey).await;
+        }
         //{ "timestamp":"2022-10-16T23:46:48Z", "event":"FSSDiscoveryScan", "Progress":0.680273, "BodyCount":21, "NonBodyCount":80, "SystemName":"Ogmar", "SystemAddress":84180519395914 }
-        "FSSDiscoveryScan" => {}//Honk
+        "FSSDiscoveryScan" => {
+            //Honk
+            let system_address = message["SystemAddress"].as_i64().unwrap();
+            let body_count = message["BodyCount"].as_i32();
+            let non_body_count = message["NonBodyCount"].as_i32();
+
+            insert_system_if_missing(&client, timestamp, message["SystemName"].as_str().unwrap(), system_address, &message["StarPos"], odyssey).await;
+
+            //language=postgresql
+            let update = "UPDATE system SET body_count = $1, non_body_count = $2 WHERE address = $3 and odyssey = $4;";
+            client.lock().await.execute(update, &[&body_count, &non_body_count, &system_address, &odyssey]).await.unwrap();
+
+            refresh_system_exploration(&client, timestamp, system_address, odyssey).await;
+        }
         "SAAScanComplete" => {
             //{ "timestamp":"2023-09-05T11:56:46Z", "event":"SAAScanComplete", "BodyName":"Byaa Broae IS-B d13-0 2 a", "SystemAddress":11148500979, "BodyID":4, "ProbesUsed":4, "EfficiencyTarget":6 }
         }
@@ -211,6 +417,14 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
             let system_address = message["SystemAddress"].as_i64().unwrap();
             let id = message["BodyID"].as_i32().unwrap();
 
+            //Rings have no body row, their signals are mining hotspots
+            //{ "timestamp":"2023-09-05T14:02:33Z", "event":"SAASignalsFound", "BodyName":"Borann A 2 A Ring", "SystemAddress":3107509474002, "BodyID":8,
+            // "Signals":[ { "Type":"LowTemperatureDiamond", "Type_Localised":"Low Temperature Diamonds", "Count":3 }, { "Type":"Opal", "Type_Localised":"Void Opals", "Count":1 } ] }
+            if let Some(ring_name) = message["BodyName"].as_str().filter(|name| name.ends_with(" Ring")) {
+                insert_ring_signals(&client, timestamp, &message, system_address, id, ring_name, odyssey).await;
+                return;
+            }
+
             if message["Signals"].len() > 0 {
                 //language=postgresql
                 let sql = "DELETE FROM body_signal WHERE system_address=$1 AND body_id=$2 AND odyssey=$3";
@@ -244,12 +458,119 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                     }
                 }
             }
+
+            //"Genuses":[ { "Genus":"$Codex_Ent_Bacterial_Genus_Name;", "Genus_Localised":"Bacterium" } ]
+            if !message["Genuses"].is_empty() {
+                //language=postgresql
+                let sql = "DELETE FROM body_genus WHERE system_address=$1 AND body_id=$2 AND odyssey=$3";
+                client.lock().await.execute(sql, &[&system_address, &id, &odyssey]).await.unwrap();
+            }
+
+            for i in 0..message["Genuses"].len() {
+                let genus = message["Genuses"][i]["Genus"].as_str().unwrap();
+
+                //language=postgresql
+                let sql = "INSERT INTO body_genus (timestamp, system_address, body_id, genus, odyssey) VALUES ($1,$2,$3,$4,$5) ON CONFLICT DO NOTHING";
+                match client.lock().await.execute(sql, &[
+                    &timestamp, &system_address, &id, &genus, &odyssey
+                ]).await {
+                    Ok(_) => {}
+                    Err(err) => {
+                        if !err.to_string().contains("violates foreign key constraint") {
+                            panic!("{}", err);
+                        }
+                    }
+                }
+            }
         }
         "FSSSignalDiscovered" => {
             //{ "timestamp":"2023-05-29T22:40:26Z", "event":"FSSSignalDiscovered", "SystemAddress":672296347049, "SignalName":"$MULTIPLAYER_SCENARIO80_TITLE;", "SignalName_Localised":"Unbewachtes Navigationssignal" }
             // { "timestamp":"2023-05-29T22:40:26Z", "event":"FSSSignalDiscovered", "SystemAddress":672296347049, "SignalName":"THE GENERAL MELCHETT X5W-0XL", "IsStation":true }
             //{ "timestamp":"2023-05-29T22:40:42Z", "event":"FSSSignalDiscovered", "SystemAddress":672296347049, "SignalName":"$USS_HighGradeEmissions;", "SignalName_Localised":"Unidentifizierte Signalquelle",
             // "USSType":"$USS_Type_ValuableSalvage;", "USSType_Localised":"Verschlüsselte Emissionen", "SpawningState":"", "SpawningFaction":"Murus Major Industry", "ThreatLevel":0, "TimeRemaining":707.545837 }
+
+            //EDDN: {"event":"FSSSignalDiscovered","SystemAddress":672296347049,"StarSystem":"Orom","StarPos":[95.5625,-19.78125,-54.59375],
+            // "signals":[{"timestamp":"2023-05-29T22:40:26Z","SignalName":"THE GENERAL MELCHETT X5W-0XL","SignalType":"FleetCarrier","IsStation":true}, ...]}
+            let system_address = message["SystemAddress"].as_i64().unwrap();
+            if let Some(system_name) = message["StarSystem"].as_str() {
+                insert_system_if_missing(&client, timestamp, system_name, system_address, &message["StarPos"], odyssey).await;
+            }
+
+            //Drop signals that have run out
+            //language=postgresql
+            let delete = "DELETE FROM system_signal WHERE expires < $1;";
+            client.lock().await.execute(delete, &[&timestamp]).await.unwrap();
+
+            //EDDN bundles the signals of a system, the journal has one event per signal
+            let signals: Vec<&JsonValue> = if message["signals"].is_array() {
+                message["signals"].members().collect()
+            } else {
+                vec![&message]
+            };
+
+            for signal in signals {
+                let name = signal["SignalName"].as_str().unwrap();
+                let signal_type = signal["SignalType"].as_str();
+                let is_station = signal["IsStation"].as_bool().unwrap_or(false);
+                let uss_type = signal["USSType"].as_str();
+                let spawning_state = signal["SpawningState"].as_str();
+                let spawning_faction = signal["SpawningFaction"].as_str();
+                let threat_level = signal["ThreatLevel"].as_i32();
+                let discovered = signal["timestamp"].as_str()
+                    .and_then(|discovered| DateTime::parse_from_rfc3339(discovered).ok())
+                    .unwrap_or(parsed_date_time)
+                    .timestamp();
+                //The journal of older clients has no SignalType, then only the name tells carriers apart
+                let is_carrier = match signal_type {
+                    Some(signal_type) => signal_type == "FleetCarrier",
+                    None => is_station && carrier_callsign(name).is_some(),
+                };
+                let expires = match signal["TimeRemaining"].as_f64() {
+                    Some(time_remaining) => Some(discovered + time_remaining as i64),
+                    None if is_carrier => Some(discovered + CARRIER_SIGNAL_TTL),
+                    None if is_station => Some(discovered + STATION_SIGNAL_TTL),
+                    None if uss_type.is_some() || signal_type == Some("USS") => Some(discovered + USS_SIGNAL_TTL),
+                    None => None,
+                };
+
+                //language=postgresql
+                let insert = "
+                    INSERT INTO system_signal
+                        (timestamp, system_address, name, type, is_station, uss_type, spawning_state, spawning_faction, threat_level, expires, odyssey)
+                    VALUES
+                        ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                    ON CONFLICT (system_address, name, coalesce(uss_type, ''), coalesce(spawning_faction, ''), odyssey) DO UPDATE SET
+                        timestamp = excluded.timestamp,
+                        type = excluded.type,
+                        is_station = excluded.is_station,
+                        spawning_state = excluded.spawning_state,
+                        threat_level = excluded.threat_level,
+                        expires = excluded.expires;";
+                match client.lock().await.execute(insert, &[
+                    &timestamp, &system_address, &name, &signal_type, &is_station, &uss_type, &spawning_state, &spawning_faction, &threat_level, &expires, &odyssey
+                ]).await {
+                    Ok(_) => {}
+                    Err(err) => {
+                        if !err.to_string().contains("violates foreign key constraint") {
+                            panic!("{}", err);
+                        }
+                    }
+                }
+
+                //Signals only carry the callsign, so only carriers already known by market id can be located
+                if signal_type == Some("FleetCarrier") {
+                    if let Some((carrier_name, callsign)) = carrier_callsign(name) {
+                        //language=postgresql
+                        let select = "SELECT market_id FROM fleet_carrier WHERE callsign = $1 and odyssey = $2;";
+                        let rows = client.lock().await.query(select, &[&callsign, &odyssey]).await.unwrap();
+                        for row in rows {
+                            let market_id: i64 = row.get(0);
+                            upsert_fleet_carrier(&client, timestamp, market_id, Some(callsign), carrier_name, None, None, odyssey).await;
+                            record_fleet_carrier_location(&client, timestamp, market_id, system_address, None, odyssey).await;
+                        }
+                    }
+                }
+            }
         }
         "Scan" => {
             let system_address = message["SystemAddress"].as_i64().unwrap();
@@ -279,6 +600,7 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
             let volcanism = message["Volcanism"].to_string();
             let discovered = message["WasDiscovered"].as_bool().unwrap();
             let mapped = message["WasMapped"].as_bool().unwrap();
+            let scan_type = message["ScanType"].as_str();
 
             let odyssey = message["odyssey"].as_bool().unwrap_or(true);
 
@@ -289,8 +611,9 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                     INSERT INTO body (timestamp, system_address, id, name, ascending_node, axial_tilt, atmosphere, distance_from_arrival_ls,
                     eccentricity, landable, mass_em, mean_anomaly, orbital_inclination, orbital_period, periapsis, class,
                     radius, rotation_period, semi_major_axis, surface_gravity,
-                    surface_pressure, surface_temperature, terraform_state, tidal_lock, volcanism, discovered, mapped,odyssey)
-                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28)
+                    surface_pressure, surface_temperature, terraform_state, tidal_lock, volcanism, discovered, mapped,odyssey, scan_type, authoritative)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26, $27, $28, $29,
+                            coalesce($29::varchar = 'NavBeaconDetail' AND (SELECT nav_beacon_scanned FROM system WHERE address = $2 AND odyssey = $28), false))
                     ON CONFLICT (system_address,id,odyssey) DO UPDATE SET
                           timestamp                = excluded.timestamp,
                           system_address              = excluded.system_address,
@@ -319,19 +642,27 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                           volcanism                = excluded.volcanism,
                           discovered               = excluded.discovered,
                           mapped                   = excluded.mapped,
-                          odyssey                  = excluded.odyssey;";
-                match client.lock().await.execute(sql, &[
+                          odyssey                  = excluded.odyssey,
+                          scan_type                = excluded.scan_type,
+                          authoritative            = CASE WHEN scan_type_rank(excluded.scan_type) > scan_type_rank(body.scan_type) THEN excluded.authoritative ELSE body.authoritative OR excluded.authoritative END
+                    WHERE scan_type_rank(excluded.scan_type) >= scan_type_rank(body.scan_type);";
+                let stored = match client.lock().await.execute(sql, &[
                     &timestamp, &system_address, &id, &name, &ascending_node, &axial_tilt, &atmosphere, &distance_from_arrival_ls,
                     &eccentricity, &landable, &mass_em, &mean_anomaly, &orbital_inclination, &orbital_period, &periapsis, &class, &radius,
                     &rotation_period, &semi_major_axis, &surface_gravity, &surface_pressure, &surface_temperature, &terraform_state, &tidal_lock,
-                    &volcanism, &discovered, &mapped, &odyssey
+                    &volcanism, &discovered, &mapped, &odyssey, &scan_type
                 ]).await {
-                    Ok(_) => {}
+                    Ok(rows) => rows > 0,
                     Err(err) => {
                         if !err.to_string().contains("violates foreign key constraint") {
                             panic!("{}", err);
                         }
+                        false
                     }
+                };
+                //A less trustworthy scan than the stored one changes nothing, compositions, rings and parents included
+                if !stored {
+                    return;
                 }
                 {
                     //Body composition
@@ -450,8 +781,9 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                 let sql = "INSERT INTO star (timestamp, system_address, name, id, absolute_magnitude, age_my, ascending_node, axial_tilt,
                     distance_from_arrival_ls, eccentricity, luminosity, mean_anomaly, orbital_inclination, orbital_period,
                     periapsis, radius, rotation_period, semi_major_axis, type, stellar_mass, subclass,
-                    surface_temperature, discovered, mapped,odyssey)
-                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25)
+                    surface_temperature, discovered, mapped,odyssey, scan_type, authoritative)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22, $23, $24, $25, $26,
+                            coalesce($26::varchar = 'NavBeaconDetail' AND (SELECT nav_beacon_scanned FROM system WHERE address = $2 AND odyssey = $25), false))
                     ON CONFLICT (odyssey,id,system_address) DO UPDATE SET
                           timestamp                = excluded.timestamp,
                           system_address              = excluded.system_address,
@@ -477,28 +809,64 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                           surface_temperature      = excluded.surface_temperature,
                           discovered               = excluded.discovered,
                           mapped                   = excluded.mapped,
-                          odyssey                  = excluded.odyssey;";
-                match client.lock().await.execute(sql, &[
+                          odyssey                  = excluded.odyssey,
+                          scan_type                = excluded.scan_type,
+                          authoritative            = CASE WHEN scan_type_rank(excluded.scan_type) > scan_type_rank(star.scan_type) THEN excluded.authoritative ELSE star.authoritative OR excluded.authoritative END
+                    WHERE scan_type_rank(excluded.scan_type) >= scan_type_rank(star.scan_type);";
+                let stored = match client.lock().await.execute(sql, &[
                     &timestamp, &system_address, &name, &id, &absolute_magnitude, &age_my, &ascending_node, &axial_tilt, &distance_from_arrival_ls, &eccentricity,
                     &luminosity, &mean_anomaly, &orbital_inclination, &orbital_period, &periapsis, &radius, &rotation_period, &semi_major_axis, &star_type, &stellar_mass, &subclass,
-                    &surface_temperature, &discovered, &mapped, &odyssey
+                    &surface_temperature, &discovered, &mapped, &odyssey, &scan_type
                 ]).await {
-                    Ok(_) => {}
+                    Ok(rows) => rows > 0,
                     Err(err) => {
                         if !err.to_string().contains("violates foreign key constraint") {
                             panic!("{}", err);
                         }
+                        false
                     }
+                };
+                //Rings and parents of a less trustworthy scan are skipped as well
+                if !stored {
+                    return;
+                }
+
+                //The arrival star is the system's main star
+                if distance_from_arrival_ls == 0.0 {
+                    update_star_class(&client, system_address, &star_type, odyssey).await;
                 }
             }
 
             if !message["Parents"].is_null() {
 
-                for i in 0..message["Parents"].len(){
-                    let entry = message["Parents"][i].entries().next().unwrap();
+                //"Parents":[ {"Planet":37}, {"Null":28}, {"Star":1}, {"Null":0} ] is the chain from the direct parent (level 0) to the root.
+                //Every parent in the chain orbits the ones after it, which also links barycentres ("Null") into the hierarchy.
+                let chain: Vec<(&str, Option<i32>)> = message["Parents"].members()
+                    .map(|parent| {
+                        let entry = parent.entries().next().unwrap();
+                        (entry.0, entry.1.as_i32())
+                    })
+                    .collect();
+                for (i, (_, child_id)) in chain.iter().enumerate() {
+                    for (level, (parent_type, parent_id)) in chain[i..].iter().enumerate().skip(1) {
+                        let level = level as i32 - 1;
+                        //language=postgresql
+                        let sql = "INSERT INTO parent(system_address, body_id, parent_type,parent_id,level,odyssey) VALUES ($1,$2,$3,$4,$5,$6) ON CONFLICT (system_address,body_id,parent_id,odyssey) DO UPDATE SET parent_type = excluded.parent_type, level = excluded.level";
+                        match client.lock().await.execute(sql,&[&system_address,child_id,parent_type,parent_id,&level,&odyssey]).await {
+                            Ok(_) => {}
+                            Err(err) => {
+                                if !err.to_string().contains("violates foreign key constraint") {
+                                    panic!("{}", err);
+                                }
+                            }
+                        }
+                    }
+                }
+                for (level, (parent_type, parent_id)) in chain.iter().enumerate() {
+                    let level = level as i32;
                     //language=postgresql
-                    let sql = "INSERT INTO parent(system_address, body_id, parent_type,parent_id,odyssey) VALUES ($1,$2,$3,$4,$5) ON CONFLICT DO NOTHING";
-                    match client.lock().await.execute(sql,&[&system_address,&id,&entry.0,&entry.1.as_i32(),&odyssey]).await {
+                    let sql = "INSERT INTO parent(system_address, body_id, parent_type,parent_id,level,odyssey) VALUES ($1,$2,$3,$4,$5,$6) ON CONFLICT (system_address,body_id,parent_id,odyssey) DO UPDATE SET parent_type = excluded.parent_type, level = excluded.level";
+                    match client.lock().await.execute(sql,&[&system_address,&id,parent_type,parent_id,&level,&odyssey]).await {
                         Ok(_) => {}
                         Err(err) => {
                             if !err.to_string().contains("violates foreign key constraint") {
@@ -528,7 +896,10 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                     let class = ring["RingClass"].as_str().unwrap();
 
                     //language=postgresql
-                    let sql = "INSERT INTO ring (timestamp, system_address, name, inner_rad, outer_rad, mass_mt, class, odyssey, body_id) VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9) ON CONFLICT (system_address, body_id, odyssey) DO NOTHING";
+                    //The ring's own body id is only known from its SAASignalsFound
+                    let sql = "INSERT INTO ring (timestamp, system_address, name, inner_rad, outer_rad, mass_mt, class, odyssey, body_id, id)
+                               VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,(SELECT max(ring_id) FROM ring_signal WHERE system_address = $2::bigint AND ring_name = $3::varchar AND odyssey = $8::boolean))
+                               ON CONFLICT (system_address, name, odyssey) DO UPDATE SET timestamp = excluded.timestamp, inner_rad = excluded.inner_rad, outer_rad = excluded.outer_rad, mass_mt = excluded.mass_mt, class = excluded.class, body_id = excluded.body_id, id = coalesce(excluded.id, ring.id)";
 
                         match client.lock().await.execute(sql,&[&timestamp,&system_address,&name,&inner_rad,&outer_rad,&mass_mt,&class,&odyssey,&id]).await {
                             Ok(_) => {}
@@ -541,6 +912,7 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                 }
             }
 
+            refresh_system_exploration(&client, timestamp, system_address, odyssey).await;
 
             match message["ScanType"].as_str().unwrap() {
                 "Detailed" => {
@@ -639,7 +1011,43 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
             }
         }
         //Planet scan with fss
-        "ScanBaryCentre" => {}
+        "ScanBaryCentre" => {
+            //{ "timestamp":"2023-09-05T13:02:11Z", "event":"ScanBaryCentre", "StarSystem":"Iota Horologii", "SystemAddress":422810995051, "BodyID":28,
+            // "SemiMajorAxis":37360806465.148926, "Eccentricity":0.079393, "OrbitalInclination":-1.176245, "Periapsis":96.052384, "OrbitalPeriod":13489838719.367676,
+            // "AscendingNode":-84.153785, "MeanAnomaly":266.416432 }
+            let system_address = message["SystemAddress"].as_i64().unwrap();
+            let body_id = message["BodyID"].as_i32().unwrap();
+            if let Some(system_name) = message["StarSystem"].as_str() {
+                insert_system_if_missing(&client, timestamp, system_name, system_address, &message["StarPos"], odyssey).await;
+            }
+
+            //language=postgresql
+            let sql = "
+                INSERT INTO barycentre (timestamp, system_address, body_id, semi_major_axis, eccentricity, orbital_inclination, periapsis, orbital_period, ascending_node, mean_anomaly, odyssey)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                ON CONFLICT (system_address, body_id, odyssey) DO UPDATE SET
+                    timestamp           = excluded.timestamp,
+                    semi_major_axis     = excluded.semi_major_axis,
+                    eccentricity        = excluded.eccentricity,
+                    orbital_inclination = excluded.orbital_inclination,
+                    periapsis           = excluded.periapsis,
+                    orbital_period      = excluded.orbital_period,
+                    ascending_node      = excluded.ascending_node,
+                    mean_anomaly        = excluded.mean_anomaly;";
+            match client.lock().await.execute(sql, &[
+                &timestamp, &system_address, &body_id,
+                &message["SemiMajorAxis"].as_f32(), &message["Eccentricity"].as_f32(), &message["OrbitalInclination"].as_f32(), &message["Periapsis"].as_f32(),
+                &message["OrbitalPeriod"].as_f32(), &message["AscendingNode"].as_f32(), &message["MeanAnomaly"].as_f32(),
+                &odyssey
+            ]).await {
+                Ok(_) => {}
+                Err(err) => {
+                    if !err.to_string().contains("violates foreign key constraint") {
+                        panic!("{}", err);
+                    }
+                }
+            }
+        }
 
         //Maintenance
         "RefuelAll" => {}
@@ -657,7 +1065,20 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
         //Docking
         "DockingRequested" => {}
         "DockingGranted" => {}
-        "Docked" => {}
+        "Docked" => {
+            //{ "timestamp":"2023-09-05T10:14:02Z", "event":"Docked", "StationName":"Jameson Memorial", "StationType":"Orbis", "Taxi":false, "Multicrew":false,
+            // "StarSystem":"Shinrarta Dezhra", "SystemAddress":3932277478106, "MarketID":128666762, "StationFaction":{ "Name":"Pilots' Federation Local Branch" },
+            // "StationGovernment":"$government_Democracy;", "StationAllegiance":"PilotsFederation", "StationServices":[ "dock", "autodock", "blackmarket", "commodities", "contacts" ],
+            // "StationEconomy":"$economy_HighTech;", "StationEconomies":[ { "Name":"$economy_HighTech;", "Proportion":0.800000 }, { "Name":"$economy_Industrial;", "Proportion":0.200000 } ],
+            // "DistFromStarLS":325.963556, "LandingPads":{ "Small":4, "Medium":8, "Large":4 } }
+            upsert_station_details(&client, timestamp, &message, message["StationName"].as_str().unwrap(), odyssey).await;
+
+            if message["StationType"].as_str() == Some("FleetCarrier") {
+                let market_id = message["MarketID"].as_i64().unwrap();
+                upsert_fleet_carrier(&client, timestamp, market_id, message["StationName"].as_str(), None, None, None, odyssey).await;
+                record_fleet_carrier_location(&client, timestamp, market_id, message["SystemAddress"].as_i64().unwrap(), None, odyssey).await;
+            }
+        }
         "Undocked" => {}
 
         //Engineer
@@ -692,7 +1113,18 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
         "LoadoutEquipModule" => {}
         "SuitLoadout" => {}
         "UseConsumable" => {}
-        "ScanOrganic" => {}
+        "ScanOrganic" => {
+            //{ "timestamp":"2023-09-05T12:03:45Z", "event":"ScanOrganic", "ScanType":"Log", "Genus":"$Codex_Ent_Bacterial_Genus_Name;", "Genus_Localised":"Bacterium",
+            // "Species":"$Codex_Ent_Bacterial_12_Name;", "Species_Localised":"Bacterium Cerbrus", "Variant":"$Codex_Ent_Bacterial_12_A_Name;",
+            // "Variant_Localised":"Bacterium Cerbrus - Teal", "SystemAddress":11148500979, "Body":4 }
+            let system_address = message["SystemAddress"].as_i64().unwrap();
+            let body_id = message["Body"].as_i32().unwrap();
+            let genus = message["Genus"].as_str().unwrap();
+            let species = message["Species"].as_str().unwrap();
+            let variant = message["Variant"].as_str();
+
+            insert_body_organic(&client, timestamp, system_address, body_id, genus, species, variant, odyssey).await;
+        }
 
         //Market
         "MarketBuy" => {}
@@ -724,6 +1156,81 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
         "MiningRefined" => {}
         "ProspectedAsteroid" => {
             //{ "timestamp":"2023-06-05T12:05:12Z", "event":"ProspectedAsteroid", "Materials":[ { "Name":"rutile", "Name_Localised":"Rutil", "Proportion":35.986309 }, { "Name":"Bauxite", "Name_Localised":"Bauxit", "Proportion":13.713245 } ], "Content":"$AsteroidMaterialContent_Low;", "Content_Localised":"Materialgehalt: Niedrig", "Remaining":100.000000 }
+            //The journal event has no location, only uploads with "SystemAddress" and the ring in "BodyName"/"Body" can be used
+            let system_address = match message["SystemAddress"].as_i64() {
+                None => { return; }
+                Some(system_address) => system_address
+            };
+            let ring_name = match message["BodyName"].as_str().or(message["Body"].as_str()) {
+                None => { return; }
+                Some(ring_name) => ring_name
+            };
+            if let Some(system_name) = message["StarSystem"].as_str() {
+                insert_system_if_missing(&client, timestamp, system_name, system_address, &message["StarPos"], odyssey).await;
+            }
+
+            //"$AsteroidMaterialContent_High;" -> "high"
+            let content = message["Content"].as_str()
+                .and_then(|content| content.strip_prefix("$AsteroidMaterialContent_"))
+                .map(|content| content.trim_end_matches(';').to_lowercase());
+            let motherlode = message["MotherlodeMaterial"].as_str().map(|material| material.to_lowercase());
+            let remaining = message["Remaining"].as_f32();
+
+            //Only counts and sums are kept, nothing about who prospected
+            //language=postgresql
+            let sql = "
+                INSERT INTO ring_mining (timestamp, system_address, ring_name, prospected, motherlodes, content_high, content_medium, content_low, remaining_sum, cracked, odyssey)
+                VALUES ($1, $2, $3, 1, CASE WHEN $4::varchar IS NULL THEN 0 ELSE 1 END,
+                        CASE WHEN $5::varchar = 'high' THEN 1 ELSE 0 END, CASE WHEN $5::varchar = 'medium' THEN 1 ELSE 0 END, CASE WHEN $5::varchar = 'low' THEN 1 ELSE 0 END,
+                        coalesce($6::real, 0), 0, $7)
+                ON CONFLICT (system_address, ring_name, odyssey) DO UPDATE SET
+                    timestamp      = excluded.timestamp,
+                    prospected     = ring_mining.prospected + 1,
+                    motherlodes    = ring_mining.motherlodes + excluded.motherlodes,
+                    content_high   = ring_mining.content_high + excluded.content_high,
+                    content_medium = ring_mining.content_medium + excluded.content_medium,
+                    content_low    = ring_mining.content_low + excluded.content_low,
+                    remaining_sum  = ring_mining.remaining_sum + excluded.remaining_sum;";
+            match client.lock().await.execute(sql, &[&timestamp, &system_address, &ring_name, &motherlode, &content, &remaining, &odyssey]).await {
+                Ok(_) => {}
+                Err(err) => {
+                    if !err.to_string().contains("violates foreign key constraint") {
+                        panic!("{}", err);
+                    }
+                }
+            }
+
+            //Materials and the motherlode material
+            let mut materials: Vec<(String, f32, i32)> = message["Materials"].members()
+                .map(|material| (material["Name"].to_string().to_lowercase(), material["Proportion"].as_f32().unwrap_or(0.0), 0))
+                .collect();
+            if let Some(motherlode) = motherlode {
+                match materials.iter_mut().find(|(name, _, _)| *name == motherlode) {
+                    None => materials.push((motherlode, 0.0, 1)),
+                    Some(material) => material.2 = 1,
+                }
+            }
+            for (name, proportion, motherlode) in materials {
+                let sample = if proportion > 0.0 { 1 } else { 0 };
+                //language=postgresql
+                let sql = "
+                    INSERT INTO ring_mining_material (timestamp, system_address, ring_name, name, samples, proportion_sum, proportion_max, motherlodes, odyssey)
+                    VALUES ($1, $2, $3, $4, $5, $6, $6, $7, $8)
+                    ON CONFLICT (system_address, ring_name, name, odyssey) DO UPDATE SET
+                        timestamp      = excluded.timestamp,
+                        samples        = ring_mining_material.samples + excluded.samples,
+                        proportion_sum = ring_mining_material.proportion_sum + excluded.proportion_sum,
+                        proportion_max = greatest(ring_mining_material.proportion_max, excluded.proportion_max),
+                        motherlodes    = ring_mining_material.motherlodes + excluded.motherlodes;";
+                match client.lock().await.execute(sql, &[&timestamp, &system_address, &ring_name, &name, &sample, &proportion, &motherlode, &odyssey]).await {
+                    Ok(_) => {}
+                    Err(err) => {
+                        if !err.to_string().contains("violates foreign key constraint") {
+                            panic!("{}", err);
+                        }
+                    }
+                }
+            }
         }
         "CargoTransfer" => {}
         "CollectCargo" => {}
@@ -743,11 +1250,64 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
         "SellOrganicData" => {}
 
         //Carrier
-        "CarrierStats" => {}
-        "CarrierJumpRequest" => {}
+        "CarrierStats" => {
+            //{ "timestamp":"2023-09-05T12:20:11Z", "event":"CarrierStats", "CarrierID":3704402432, "Callsign":"Q2K-BHB", "Name":"EDCAS", "DockingAccess":"all", "AllowNotorious":false,
+            // "FuelLevel":879, "JumpRangeCurr":500.000000, "JumpRangeMax":500.000000, "PendingDecommission":false, "SpaceUsage":{ ... }, "Finance":{ ... },
+            // "Crew":[ { "CrewRole":"BlackMarket", "Activated":false }, { "CrewRole":"Captain", "Activated":true, "Enabled":true, "CrewName":"Vada Cannon" },
+            // { "CrewRole":"Refuel", "Activated":true, "Enabled":true, "CrewName":"Donna Moon" }, { "CrewRole":"Bartender", "Activated":true, "Enabled":true, "CrewName":"Delia Tate" } ],
+            // "ShipPacks":[ ], "ModulePacks":[ ] }
+            let market_id = message["CarrierID"].as_i64().unwrap();
+            let callsign = message["Callsign"].as_str();
+            upsert_fleet_carrier(&client, timestamp, market_id, callsign, message["Name"].as_str(), message["DockingAccess"].as_str(), message["AllowNotorious"].as_bool(), odyssey).await;
+
+            //Carriers are stations as well, their services are kept in station_service
+            //language=postgresql
+            let sql = "INSERT INTO station (timestamp, name, market_id, type, odyssey) VALUES ($1,$2,$3,'FleetCarrier',$4) ON CONFLICT (market_id,odyssey) DO UPDATE SET timestamp = excluded.timestamp, name = coalesce(excluded.name, station.name), type = excluded.type;";
+            client.lock().await.execute(sql, &[&timestamp, &callsign, &market_id, &odyssey]).await.unwrap();
+
+            //Only the optional crew services are touched, the rest of StationServices comes from Docked/Location
+            for i in 0..message["Crew"].len() {
+                let crew = &message["Crew"][i];
+                let role = crew["CrewRole"].as_str().unwrap();
+                if role == "Captain" {
+                    continue;
+                }
+                //CrewRole "BlackMarket" -> service "blackmarket" like in StationServices
+                let service = role.to_lowercase();
+                if crew["Activated"].as_bool().unwrap_or(false) && crew["Enabled"].as_bool().unwrap_or(true) {
+                    //language=postgresql
+                    let insert = "INSERT INTO station_service (timestamp, market_id, service, odyssey) VALUES ($1,$2,$3,$4) ON CONFLICT DO NOTHING;";
+                    client.lock().await.execute(insert, &[&timestamp, &market_id, &service, &odyssey]).await.unwrap();
+                } else {
+                    //language=postgresql
+                    let delete = "DELETE FROM station_service WHERE market_id=$1 and service=$2 and odyssey=$3;";
+                    client.lock().await.execute(delete, &[&market_id, &service, &odyssey]).await.unwrap();
+                }
+            }
+        }
+        "CarrierJumpRequest" => {
+            //{ "timestamp":"2023-09-05T12:05:41Z", "event":"CarrierJumpRequest", "CarrierID":3704402432, "SystemName":"Colonia", "Body":"Colonia 2", "SystemAddress":3238296097059,
+            // "BodyID":13, "DepartureTime":"2023-09-05T12:20:10Z" }
+            let market_id = message["CarrierID"].as_i64().unwrap();
+            let destination = message["SystemAddress"].as_i64();
+            let departure = message["DepartureTime"].as_str()
+                .and_then(|departure| DateTime::parse_from_rfc3339(departure).ok())
+                .map(|departure| departure.timestamp());
+
+            upsert_fleet_carrier(&client, timestamp, market_id, None, None, None, None, odyssey).await;
+            //language=postgresql
+            let update = "UPDATE fleet_carrier SET jump_destination_address = $1, jump_departure = $2 WHERE market_id = $3 and odyssey = $4;";
+            client.lock().await.execute(update, &[&destination, &departure, &market_id, &odyssey]).await.unwrap();
+        }
         "CarrierTradeOrder" => {}
         "CarrierFinance" => {}
-        "CarrierJumpCancelled" => {}
+        "CarrierJumpCancelled" => {
+            //{ "timestamp":"2023-09-05T12:07:12Z", "event":"CarrierJumpCancelled", "CarrierID":3704402432 }
+            let market_id = message["CarrierID"].as_i64().unwrap();
+            //language=postgresql
+            let update = "UPDATE fleet_carrier SET jump_destination_address = NULL, jump_departure = NULL WHERE market_id = $1 and odyssey = $2;";
+            client.lock().await.execute(update, &[&market_id, &odyssey]).await.unwrap();
+        }
         "CarrierDepositFuel" => {}
         "CarrierDockingPermission" => {}
         "CarrierCrewServices" => {}
@@ -780,7 +1340,58 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
         "LoadGame" => {}
         "SquadronStartup" => {}
         "Music" => {}
-        "CodexEntry" => {}
+        "CodexEntry" => {
+            //{ "timestamp":"2023-09-05T12:03:45Z", "event":"CodexEntry", "EntryID":2420602, "Name":"$Codex_Ent_Bacterial_12_A_Name;", "SubCategory":"$Codex_SubCategory_Organic_Structures;",
+            // "Category":"$Codex_Category_Biology;", "Region":"$Codex_RegionName_18;", "System":"Byaa Broae IS-B d13-0", "SystemAddress":11148500979, "BodyID":4,
+            // "Latitude":-12.514395, "Longitude":87.021332 }
+            let system_address = message["SystemAddress"].as_i64().unwrap();
+            let entry_id = message["EntryID"].as_i64().unwrap();
+            let name = message["Name"].as_str();
+            let category = message["Category"].as_str();
+            let subcategory = message["SubCategory"].as_str();
+            let region = message["Region"].as_str();
+            let body_id = message["BodyID"].as_i32();
+            let latitude = message["Latitude"].as_f32();
+            let longitude = message["Longitude"].as_f32();
+            let reported = parsed_date_time.timestamp();
+
+            if let Some(system_name) = message["System"].as_str() {
+                insert_system_if_missing(&client, timestamp, system_name, system_address, &message["StarPos"], odyssey).await;
+            }
+
+            //language=postgresql
+            let insert = "
+                INSERT INTO codex_entry
+                    (timestamp, first_reported, system_address, body_id, entry_id, name, category, subcategory, region, latitude, longitude, odyssey)
+                VALUES
+                    ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+                ON CONFLICT (system_address, coalesce(body_id, -1), entry_id, odyssey) DO UPDATE SET
+                    timestamp = excluded.timestamp,
+                    first_reported = least(codex_entry.first_reported, excluded.first_reported),
+                    name = excluded.name,
+                    category = excluded.category,
+                    subcategory = excluded.subcategory,
+                    region = excluded.region,
+                    latitude = coalesce(codex_entry.latitude, excluded.latitude),
+                    longitude = coalesce(codex_entry.longitude, excluded.longitude);";
+            match client.lock().await.execute(insert, &[
+                &timestamp, &reported, &system_address, &body_id, &entry_id, &name, &category, &subcategory, &region, &latitude, &longitude, &odyssey
+            ]).await {
+                Ok(_) => {}
+                Err(err) => {
+                    if !err.to_string().contains("violates foreign key constraint") {
+                        panic!("{}", err);
+                    }
+                }
+            }
+
+            if message["SubCategory"].as_str() == Some("$Codex_SubCategory_Organic_Structures;") {
+                let variant = message["Name"].as_str().unwrap();
+                if let (Some(body_id), Some((genus, species))) = (message["BodyID"].as_i32(), organic_names(variant)) {
+                    insert_body_organic(&client, timestamp, system_address, body_id, &genus, &species, Some(variant), odyssey).await;
+                }
+            }
+        }
         "Rank" => {}
         "Progress" => {}
         "Reputation" => {}
@@ -802,7 +1413,22 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
         "SearchAndRescue" => {}
         "HeatDamage" => {}
         "CommunityGoalReward" => {}
-        "NavBeaconScan" => {}
+        "NavBeaconScan" => {
+            //{ "timestamp":"2023-09-05T12:41:02Z", "event":"NavBeaconScan", "SystemAddress":422810995051, "NumBodies":30 }
+            //EDDN adds "StarSystem" and "StarPos"
+            let system_address = message["SystemAddress"].as_i64().unwrap();
+            let body_count = message["NumBodies"].as_i32();
+            if let Some(system_name) = message["StarSystem"].as_str() {
+                insert_system_if_missing(&client, timestamp, system_name, system_address, &message["StarPos"], odyssey).await;
+            }
+
+            //The nav beacon knows every body of the system, NavBeaconDetail scans following it are authoritative
+            //language=postgresql
+            let update = "UPDATE system SET body_count = coalesce($1, body_count), nav_beacon_scanned = true WHERE address = $2 and odyssey = $3;";
+            client.lock().await.execute(update, &[&body_count, &system_address, &odyssey]).await.unwrap();
+
+            refresh_system_exploration(&client, timestamp, system_address, odyssey).await;
+        }
         "USSDrop" => {}
         "Interdicted" => {}
         "Promotion" => {}
@@ -821,11 +1447,90 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
         "SharedBookmarkToSquadron" => {}
         "MaterialDiscovered" => {}
         "SetUserShipName" => {}
-        "FCMaterials" => {}
+        "FCMaterials" => {
+            //fcmaterials_journal:
+            //{ "timestamp":"2023-09-05T12:33:50Z", "event":"FCMaterials", "MarketID":3704402432, "CarrierName":"EDCAS", "CarrierID":"Q2K-BHB",
+            // "Items":[ { "id":128961524, "Name":"$aerogel_name;", "Name_Localised":"Aerogel", "Price":500, "Stock":0, "Demand":10 } ] }
+            //fcmaterials_capi:
+            //{ "timestamp":"2023-09-05T12:33:50Z", "event":"FCMaterials", "MarketID":3704402432, "CarrierID":"Q2K-BHB",
+            // "Items":{ "purchases":[ { "name":"Aerogel", "outstanding":10, "price":500, "total":10 } ], "sales":{ "128961524":{ "id":128961524, "name":"ChemicalCatalyst", "price":400, "stock":5 } } } }
+            let market_id = message["MarketID"].as_i64().unwrap();
+            upsert_fleet_carrier(&client, timestamp, market_id, message["CarrierID"].as_str(), message["CarrierName"].as_str(), None, None, odyssey).await;
+
+            //The inventory is always sent as a whole
+            //language=postgresql
+            let delete = "DELETE FROM fc_material WHERE market_id=$1 and odyssey=$2;";
+            client.lock().await.execute(delete, &[&market_id, &odyssey]).await.unwrap();
+
+            //buy_price/demand is what the bartender buys from players, sell_price/stock what it sells to them.
+            //Capi sends the same material as purchase and sale separately, so both sides are merged per name
+            //language=postgresql
+            let insert = "INSERT INTO fc_material (timestamp, market_id, material_id, name, buy_price, sell_price, stock, demand, odyssey) VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9)
+                          ON CONFLICT (market_id, name, odyssey) DO UPDATE SET
+                              material_id = coalesce(excluded.material_id, fc_material.material_id),
+                              buy_price = coalesce(excluded.buy_price, fc_material.buy_price),
+                              sell_price = coalesce(excluded.sell_price, fc_material.sell_price),
+                              stock = coalesce(excluded.stock, fc_material.stock),
+                              demand = coalesce(excluded.demand, fc_material.demand);";
+            let items = &message["Items"];
+            //(material id, name, buy price, sell price, stock, demand)
+            type Material = (Option<i64>, String, Option<i32>, Option<i32>, Option<i32>, Option<i32>);
+            let mut materials: Vec<Material> = Vec::new();
+            if items.is_array() {
+                //The journal has one price per material, it is a buy order if there is demand and a sell order if there is stock
+                for item in items.members() {
+                    let price = item["Price"].as_i32();
+                    let stock = item["Stock"].as_i32();
+                    let demand = item["Demand"].as_i32();
+                    let buy_price = price.filter(|_| demand.unwrap_or(0) > 0);
+                    let sell_price = price.filter(|_| stock.unwrap_or(0) > 0);
+                    materials.push((item["id"].as_i64(), fc_material_name(item["Name"].as_str().unwrap()), buy_price, sell_price, stock, demand));
+                }
+            } else {
+                for item in items["purchases"].members() {
+                    materials.push((None, fc_material_name(item["name"].as_str().unwrap()), item["price"].as_i32(), None, None, item["outstanding"].as_i32()));
+                }
+                //sales is an empty array if nothing is sold, otherwise an object keyed by id
+                let sales: Vec<&JsonValue> = if items["sales"].is_array() {
+                    items["sales"].members().collect()
+                } else {
+                    items["sales"].entries().map(|(_, sale)| sale).collect()
+                };
+                for item in sales {
+                    materials.push((item["id"].as_i64(), fc_material_name(item["name"].as_str().unwrap()), None, item["price"].as_i32(), item["stock"].as_i32(), None));
+                }
+            }
+            for (material_id, name, buy_price, sell_price, stock, demand) in materials {
+                client.lock().await.execute(insert, &[&timestamp, &market_id, &material_id, &name, &buy_price, &sell_price, &stock, &demand, &odyssey]).await.unwrap();
+            }
+        }
         "CommunityGoalJoin" => {}
         "SupercruiseDestinationDrop" => {}
         "JetConeBoost" => {}
-        "AsteroidCracked" => {}
+        "AsteroidCracked" => {
+            //{ "timestamp":"2023-06-05T12:09:41Z", "event":"AsteroidCracked", "Body":"Borann A 2 A Ring" }
+            //Like ProspectedAsteroid only usable with an uploader-added "SystemAddress"
+            if let (Some(system_address), Some(ring_name)) = (message["SystemAddress"].as_i64(), message["BodyName"].as_str().or(message["Body"].as_str())) {
+                if let Some(system_name) = message["StarSystem"].as_str() {
+                    insert_system_if_missing(&client, timestamp, system_name, system_address, &message["StarPos"], odyssey).await;
+                }
+                //language=postgresql
+                let sql = "
+                    INSERT INTO ring_mining (timestamp, system_address, ring_name, prospected, motherlodes, content_high, content_medium, content_low, remaining_sum, cracked, odyssey)
+                    VALUES ($1, $2, $3, 0, 0, 0, 0, 0, 0, 1, $4)
+                    ON CONFLICT (system_address, ring_name, odyssey) DO UPDATE SET
+                        timestamp = excluded.timestamp,
+                        cracked   = ring_mining.cracked + 1;";
+                match client.lock().await.execute(sql, &[&timestamp, &system_address, &ring_name, &odyssey]).await {
+                    Ok(_) => {}
+                    Err(err) => {
+                        if !err.to_string().contains("violates foreign key constraint") {
+                            panic!("{}", err);
+                        }
+                    }
+                }
+            }
+        }
         "EscapeInterdiction" => {}
         "TechnologyBroker" => {}
         "NavBeaconDetail" => {}
@@ -846,8 +1551,9 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
             if !message["ships"].is_null() {
                 //ships
                 {
+                    //The system name only resolves to an address if no other system shares it, otherwise the stored address is kept
                     //language=postgresql
-                    let sql = "INSERT INTO station VALUES ($1,$2,$3,$4,$5) ON CONFLICT (market_id,odyssey) DO UPDATE SET timestamp = excluded.timestamp, name = excluded.name, market_id = excluded.market_id, system_name = excluded.system_name;";
+                    let sql = "INSERT INTO station (timestamp, name, market_id, system_name, system_address, odyssey) VALUES ($1,$2,$3,$4,(SELECT min(address) FROM system WHERE lower(name) = lower($4::varchar) AND odyssey = $5 HAVING count(*) = 1),$5) ON CONFLICT (market_id,odyssey) DO UPDATE SET timestamp = excluded.timestamp, name = excluded.name, market_id = excluded.market_id, system_name = excluded.system_name, system_address = coalesce(excluded.system_address, station.system_address);";
                     match client.lock().await.execute(sql, &[
                         &timestamp,
                         &station_name,
@@ -863,6 +1569,11 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                         }
                     }
                 }
+                {
+                    let ships: Vec<String> = message["ships"].members().map(catalogue::normalize_symbol).collect();
+                    record_market_item_changes(&client, timestamp, market_id, "ship", "ship", &ships, odyssey).await;
+                    catalogue::insert_ships(&client, &ships).await;
+                }
                 {
                     //language=postgresql
                     let delete = "DELETE FROM ship WHERE market_id=$1 and odyssey=$2;";
@@ -876,7 +1587,7 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                     match client.lock().await.execute(insert, &[
                         &timestamp,
                         &market_id,
-                        &message["ships"][i].to_string(),
+                        &catalogue::normalize_symbol(&message["ships"][i]),
                         &odyssey
                     ]).await {
                         Ok(_) => {}
@@ -892,7 +1603,7 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                     //modules
                     {
                         //language=postgresql
-                        let sql = "INSERT INTO station (timestamp, name, market_id, system_name, odyssey) VALUES ($1,$2,$3,$4,$5) ON CONFLICT (market_id,odyssey) DO UPDATE SET timestamp = excluded.timestamp, name = excluded.name, market_id = excluded.market_id, system_name = excluded.system_name, odyssey = excluded.odyssey;";
+                        let sql = "INSERT INTO station (timestamp, name, market_id, system_name, system_address, odyssey) VALUES ($1,$2,$3,$4,(SELECT min(address) FROM system WHERE lower(name) = lower($4::varchar) AND odyssey = $5 HAVING count(*) = 1),$5) ON CONFLICT (market_id,odyssey) DO UPDATE SET timestamp = excluded.timestamp, name = excluded.name, market_id = excluded.market_id, system_name = excluded.system_name, system_address = coalesce(excluded.system_address, station.system_address);";
                         match client.lock().await.execute(sql, &[
                             &timestamp,
                             &station_name,
@@ -908,6 +1619,11 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                             }
                         }
                     }
+                    {
+                        let modules: Vec<String> = message["modules"].members().map(catalogue::normalize_symbol).collect();
+                        record_market_item_changes(&client, timestamp, market_id, "module", "name", &modules, odyssey).await;
+                        catalogue::insert_modules(&client, &modules).await;
+                    }
                     {
                         //language=postgresql
                         let delete = "DELETE FROM module WHERE market_id=$1 and odyssey=$2;";
@@ -919,7 +1635,7 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                         match client.lock().await.execute(insert, &[
                             &timestamp,
                             &market_id,
-                            &message["modules"][i].to_string(),
+                            &catalogue::normalize_symbol(&message["modules"][i]),
                             &odyssey
                         ]).await {
                             Ok(_) => {}
@@ -935,7 +1651,7 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                         //commodities
                         {
                             //language=postgresql
-                            let sql = "INSERT INTO station VALUES ($1,$2,$3,$4,$5) ON CONFLICT (market_id,odyssey) DO UPDATE SET timestamp = excluded.timestamp, name = excluded.name, market_id = excluded.market_id, system_name = excluded.system_name;";
+                            let sql = "INSERT INTO station (timestamp, name, market_id, system_name, system_address, odyssey) VALUES ($1,$2,$3,$4,(SELECT min(address) FROM system WHERE lower(name) = lower($4::varchar) AND odyssey = $5 HAVING count(*) = 1),$5) ON CONFLICT (market_id,odyssey) DO UPDATE SET timestamp = excluded.timestamp, name = excluded.name, market_id = excluded.market_id, system_name = excluded.system_name, system_address = coalesce(excluded.system_address, station.system_address);";
                             match client.lock().await.execute(sql, &[
                                 &timestamp,
                                 &station_name,
@@ -958,11 +1674,61 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                             let delete = "DELETE FROM commodity WHERE market_id=$1 and odyssey=$2;";
                             client.lock().await.execute(delete, &[&market_id, &odyssey]).await.unwrap();
                         }
+                        //"prohibited":["BattleWeapons","Slaves"]
+                        if !message["prohibited"].is_null() {
+                            //language=postgresql
+                            let delete = "DELETE FROM station_prohibited WHERE market_id=$1 and odyssey=$2;";
+                            client.lock().await.execute(delete, &[&market_id, &odyssey]).await.unwrap();
+                        }
+                        for i in 0..message["prohibited"].len() {
+                            //language=postgresql
+                            let insert = "INSERT INTO station_prohibited (timestamp, market_id, name, odyssey) VALUES ($1,$2,$3,$4) ON CONFLICT DO NOTHING;";
+                            match client.lock().await.execute(insert, &[
+                                &timestamp,
+                                &market_id,
+                                &message["prohibited"][i].to_string().to_lowercase(),
+                                &odyssey
+                            ]).await {
+                                Ok(_) => {}
+                                Err(err) => {
+                                    if !err.to_string().contains("violates foreign key constraint") {
+                                        panic!("{}", err);
+                                    }
+                                }
+                            }
+                        }
+                        //"economies":[{"name":"Extraction","proportion":0.8},{"name":"Refinery","proportion":0.2}]
+                        if !message["economies"].is_empty() {
+                            //language=postgresql
+                            let delete = "DELETE FROM station_economy WHERE market_id=$1 and odyssey=$2;";
+                            client.lock().await.execute(delete, &[&market_id, &odyssey]).await.unwrap();
+                        }
+                        for i in 0..message["economies"].len() {
+                            //Stored as the journal symbol ("$economy_Extraction;") like StationEconomies from Docked
+                            let economy = format!("$economy_{};", message["economies"][i]["name"]);
+                            let proportion = message["economies"][i]["proportion"].as_f32();
+                            //language=postgresql
+                            let insert = "INSERT INTO station_economy (timestamp, market_id, name, proportion, odyssey) VALUES ($1,$2,$3,$4,$5) ON CONFLICT (market_id, name, odyssey) DO UPDATE SET proportion = excluded.proportion;";
+                            match client.lock().await.execute(insert, &[&timestamp, &market_id, &economy, &proportion, &odyssey]).await {
+                                Ok(_) => {}
+                                Err(err) => {
+                                    if !err.to_string().contains("violates foreign key constraint") {
+                                        panic!("{}", err);
+                                    }
+                                }
+                            }
+                        }
                         for i in 0..message["commodities"].len() {
                             {
                                 //language=postgresql
-                                let insert = "INSERT INTO commodity (timestamp, market_id, name, buy_price, sell_price, mean_price, demand, demand_bracket, stock, stock_bracket, odyssey) VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11) ON CONFLICT (name,market_id,odyssey) DO UPDATE SET timestamp = excluded.timestamp, market_id = excluded.market_id, name = excluded.name, buy_price = excluded.buy_price, sell_price = excluded.sell_price,
-                                                                                                                     mean_price = excluded.mean_price, demand=excluded.demand, demand_bracket = excluded.demand_bracket, stock = excluded.stock, stock_bracket = excluded.stock_bracket, odyssey = excluded.odyssey;";
+                                let insert = "INSERT INTO commodity (timestamp, market_id, name, buy_price, sell_price, mean_price, demand, demand_bracket, stock, stock_bracket, status_flags, odyssey) VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9,$10,$11,$12) ON CONFLICT (name,market_id,odyssey) DO UPDATE SET timestamp = excluded.timestamp, market_id = excluded.market_id, name = excluded.name, buy_price = excluded.buy_price, sell_price = excluded.sell_price,
+                                                                                                                     mean_price = excluded.mean_price, demand=excluded.demand, demand_bracket = excluded.demand_bracket, stock = excluded.stock, stock_bracket = excluded.stock_bracket, status_flags = excluded.status_flags, odyssey = excluded.odyssey;";
+                                //"statusFlags":["Rare"]
+                                let status_flags: Option<Vec<String>> = if message["commodities"][i]["statusFlags"].is_array() {
+                                    Some(message["commodities"][i]["statusFlags"].members().map(|flag| flag.to_string()).collect())
+                                } else {
+                                    None
+                                };
 
                                 match client.lock().await.execute(insert, &[
                                     &timestamp,
@@ -975,6 +1741,7 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                                     &message["commodities"][i]["demandBracket"].as_i32(),
                                     &message["commodities"][i]["stock"].as_i32().unwrap(),
                                     &message["commodities"][i]["stockBracket"].as_i32(),
+                                    &status_flags,
                                     &odyssey
                                 ]).await {
                                     Ok(_) => {}
@@ -985,114 +1752,98 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
                                     }
                                 }
                             }
-                            {
-                                //language=postgresql
-                                let select = "SELECT timestamp from commodity_history where name=$1 and odyssey=$2 order by timestamp desc limit 1;";
-                                let result = client.lock().await.query(select,&[
-                                    &message["commodities"][i]["name"].to_string().to_lowercase(),
-                                    &odyssey
-                                ]).await;
-
-                                match result {
-                                    Ok(res) => {
-                                        match res.first(){
-                                            None => {
-                                                //No data yet available
-
-                                                //language=postgresql
-                                                let min_select = "SELECT min(buy_price) FROM commodity WHERE odyssey=$1 and name=$2 and stock > 1000;";
-                                                let min_row = client.lock().await.query_one(min_select,&[&odyssey,&message["commodities"][i]["name"].to_string().to_lowercase()]).await.unwrap();
-                                                let min = match min_row.try_get::<usize,i32>(0) {
-                                                    Ok(min) => {min},
-                                                    Err(_) => {
-                                                        //language=postgresql
-                                                        let min_select = "SELECT min(buy_price) FROM commodity WHERE odyssey=$1 and name=$2;";
-                                                        let min_row = client.lock().await.query_one(min_select,&[&odyssey,&message["commodities"][i]["name"].to_string().to_lowercase()]).await.unwrap();
-                                                        min_row.get::<usize,i32>(0)
-                                                    },
-                                                };
-
-                                                //language=postgresql
-                                                let max_select = "SELECT max(sell_price) FROM commodity WHERE odyssey=$1 and name=$2 and demand > 1000;";
-                                                let max_row = client.lock().await.query_one(max_select,&[&odyssey,&message["commodities"][i]["name"].to_string().to_lowercase()]).await.unwrap();
-                                                let max = match max_row.try_get::<usize,i32>(0) {
-                                                    Ok(max) => {max},
-                                                    Err(_) => {
-                                                        //language=postgresql
-                                                        let max_select = "SELECT max(sell_price) FROM commodity WHERE odyssey=$1 and name=$2;";
-                                                        let max_row = client.lock().await.query_one(max_select,&[&odyssey,&message["commodities"][i]["name"].to_string().to_lowercase()]).await.unwrap();
-                                                        max_row.get::<usize,i32>(0)
-                                                    },
-                                                };
-                                                //language=postgresql
-                                                let avg_select = "SELECT distinct CAST(AVG(mean_price) OVER () as INTEGER) as avg_mean_price FROM commodity WHERE odyssey=$1 and name=$2;";
-                                                let avg = client.lock().await.query_one(avg_select,&[&odyssey,&message["commodities"][i]["name"].to_string().to_lowercase()]).await.unwrap();
-
-                                                //language=postgresql
-                                                let insert = "INSERT INTO commodity_history VALUES ($1,$2,$3,$4,$5,$6);";
-                                                client.lock().await.execute(insert, &[
-                                                    &timestamp,
-                                                    &message["commodities"][i]["name"].to_string().to_lowercase(),
-                                                    &min,
-                                                    &max,
-                                                    &avg.get::<usize,i32>(0),
-                                                    &odyssey
-                                                ]).await.unwrap();
-                                            }
-                                            Some(row) => {
-                                                let commodity_timestamp: i64 = row.get(0);
-                                                //Insert only if the timestamp of the new commodity is older than an hour
-                                                //timestamp has its timestamp in seconds since 1970
-                                                if timestamp - commodity_timestamp > 60*60 {
-                                                    //language=postgresql
-                                                    let min_select = "SELECT min(buy_price) FROM commodity WHERE odyssey=$1 and name=$2 and stock > 1000;";
-                                                    let min_row = client.lock().await.query_one(min_select,&[&odyssey,&message["commodities"][i]["name"].to_string().to_lowercase()]).await.unwrap();
-                                                    let min = match min_row.try_get::<usize,i32>(0) {
-                                                        Ok(min) => {min},
-                                                        Err(_) => {
-                                                            //language=postgresql
-                                                            let min_select = "SELECT min(buy_price) FROM commodity WHERE odyssey=$1 and name=$2;";
-                                                            let min_row = client.lock().await.query_one(min_select,&[&odyssey,&message["commodities"][i]["name"].to_string().to_lowercase()]).await.unwrap();
-                                                            min_row.get::<usize,i32>(0)
-                                                        },
-                                                    };
-
-                                                    //language=postgresql
-                                                    let max_select = "SELECT max(sell_price) FROM commodity WHERE odyssey=$1 and name=$2 and demand > 1000;";
-                                                    let max_row = client.lock().await.query_one(max_select,&[&odyssey,&message["commodities"][i]["name"].to_string().to_lowercase()]).await.unwrap();
-                                                    let max = match max_row.try_get::<usize,i32>(0) {
-                                                        Ok(max) => {max},
-                                                        Err(_) => {
-                                                            //language=postgresql
-                                                            let max_select = "SELECT max(sell_price) FROM commodity WHERE odyssey=$1 and name=$2;";
-                                                            let max_row = client.lock().await.query_one(max_select,&[&odyssey,&message["commodities"][i]["name"].to_string().to_lowercase()]).await.unwrap();
-                                                            max_row.get::<usize,i32>(0)
-                                                        },
-                                                    };
-
-                                                    //language=postgresql
-                                                    let avg_select = "SELECT distinct CAST(AVG(mean_price) OVER () as INTEGER) as avg_mean_price FROM commodity WHERE odyssey=$1 and name=$2;";
-                                                    let avg = client.lock().await.query_one(avg_select,&[&odyssey,&message["commodities"][i]["name"].to_string().to_lowercase()]).await.unwrap();
-
-                                                    //language=postgresql
-                                                    let insert = "INSERT INTO commodity_history VALUES ($1,$2,$3,$4,$5,$6);";
-                                                    client.lock().await.execute(insert, &[
-                                                        &timestamp,
-                                                        &message["commodities"][i]["name"].to_string().to_lowercase(),
-                                                        &min,
-                                                        &max,
-                                                        &avg.get::<usize,i32>(0),
-                                                        &odyssey
-                                                    ]).await.unwrap();
-                                                }
-                                            }
-                                        }
+                        }
+                        {
+                            //History and candles of all commodities in one statement per table
+                            let names: Vec<String> = message["commodities"].members().map(|commodity| commodity["name"].to_string().to_lowercase()).collect();
+                            let buy_prices: Vec<i32> = message["commodities"].members().map(|commodity| commodity["buyPrice"].as_i32().unwrap()).collect();
+                            let sell_prices: Vec<i32> = message["commodities"].members().map(|commodity| commodity["sellPrice"].as_i32().unwrap()).collect();
+                            let demands: Vec<i32> = message["commodities"].members().map(|commodity| commodity["demand"].as_i32().unwrap()).collect();
+                            let demand_brackets: Vec<Option<i32>> = message["commodities"].members().map(|commodity| commodity["demandBracket"].as_i32()).collect();
+                            let stocks: Vec<i32> = message["commodities"].members().map(|commodity| commodity["stock"].as_i32().unwrap()).collect();
+                            let stock_brackets: Vec<Option<i32>> = message["commodities"].members().map(|commodity| commodity["stockBracket"].as_i32()).collect();
+
+                            //Only adds a row for commodities that changed since the last known prices of this market
+                            //language=postgresql
+                            let insert = "
+                                INSERT INTO commodity_price_history (timestamp, market_id, name, buy_price, sell_price, demand, demand_bracket, stock, stock_bracket, odyssey)
+                                SELECT $1::bigint, $2::bigint, prices.name, prices.buy, prices.sell, prices.demand, prices.demand_bracket, prices.stock, prices.stock_bracket, $3::boolean
+                                FROM unnest($4::varchar[], $5::integer[], $6::integer[], $7::integer[], $8::integer[], $9::integer[], $10::integer[])
+                                         AS prices(name, buy, sell, demand, demand_bracket, stock, stock_bracket)
+                                WHERE NOT EXISTS (
+                                    SELECT 1 FROM (
+                                        SELECT buy_price, sell_price, demand, demand_bracket, stock, stock_bracket FROM commodity_price_history
+                                        WHERE market_id = $2 AND name = prices.name AND odyssey = $3
+                                        ORDER BY timestamp DESC LIMIT 1
+                                    ) latest
+                                    WHERE latest.buy_price IS NOT DISTINCT FROM prices.buy AND latest.sell_price IS NOT DISTINCT FROM prices.sell
+                                      AND latest.demand IS NOT DISTINCT FROM prices.demand AND latest.demand_bracket IS NOT DISTINCT FROM prices.demand_bracket
+                                      AND latest.stock IS NOT DISTINCT FROM prices.stock AND latest.stock_bracket IS NOT DISTINCT FROM prices.stock_bracket
+                                )
+                                ON CONFLICT (market_id, name, timestamp, odyssey) DO NOTHING;";
+                            match client.lock().await.execute(insert, &[
+                                &timestamp, &market_id, &odyssey, &names, &buy_prices, &sell_prices, &demands, &demand_brackets, &stocks, &stock_brackets
+                            ]).await {
+                                Ok(_) => {}
+                                Err(err) => {
+                                    if !err.to_string().contains("violates foreign key constraint") {
+                                        panic!("{}", err);
                                     }
-                                    Err(err) => {
-                                        println!("commodity_history select: {}",err);
+                                }
+                            }
+
+                            //Candles, a price of 0 means the commodity isn't bought/sold
+                            //language=postgresql
+                            let insert = "
+                                INSERT INTO market_commodity_candle (market_id, name, resolution, bucket, buy_open, buy_high, buy_low, buy_close, sell_open, sell_high, sell_low, sell_close, timestamp, odyssey)
+                                SELECT $1::bigint, prices.name, resolutions.resolution,
+                                       CAST(extract(epoch from date_trunc(resolutions.resolution, to_timestamp($2::bigint) at time zone 'UTC')) AS BIGINT),
+                                       nullif(prices.buy, 0), nullif(prices.buy, 0), nullif(prices.buy, 0), nullif(prices.buy, 0),
+                                       nullif(prices.sell, 0), nullif(prices.sell, 0), nullif(prices.sell, 0), nullif(prices.sell, 0),
+                                       $2::bigint, $3::boolean
+                                FROM unnest($4::varchar[], $5::integer[], $6::integer[]) AS prices(name, buy, sell)
+                                         CROSS JOIN unnest(array['hour', 'day', 'week']) AS resolutions(resolution)
+                                ON CONFLICT (market_id, name, resolution, bucket, odyssey) DO UPDATE SET
+                                    buy_open = coalesce(market_commodity_candle.buy_open, excluded.buy_open),
+                                    buy_high = greatest(market_commodity_candle.buy_high, excluded.buy_high),
+                                    buy_low = least(market_commodity_candle.buy_low, excluded.buy_low),
+                                    buy_close = coalesce(excluded.buy_close, market_commodity_candle.buy_close),
+                                    sell_open = coalesce(market_commodity_candle.sell_open, excluded.sell_open),
+                                    sell_high = greatest(market_commodity_candle.sell_high, excluded.sell_high),
+                                    sell_low = least(market_commodity_candle.sell_low, excluded.sell_low),
+                                    sell_close = coalesce(excluded.sell_close, market_commodity_candle.sell_close),
+                                    timestamp = excluded.timestamp;";
+                            match client.lock().await.execute(insert, &[&market_id, &timestamp, &odyssey, &names, &buy_prices, &sell_prices]).await {
+                                Ok(_) => {}
+                                Err(err) => {
+                                    if !err.to_string().contains("violates foreign key constraint") {
+                                        panic!("{}", err);
                                     }
                                 }
                             }
+
+                            //Galaxy-wide candles take every market's price as a trade at that price
+                            //language=postgresql
+                            let insert = "
+                                INSERT INTO commodity_candle (name, resolution, bucket, buy_open, buy_high, buy_low, buy_close, sell_open, sell_high, sell_low, sell_close, timestamp, odyssey)
+                                SELECT prices.name, resolutions.resolution,
+                                       CAST(extract(epoch from date_trunc(resolutions.resolution, to_timestamp($1::bigint) at time zone 'UTC')) AS BIGINT),
+                                       nullif(prices.buy, 0), nullif(prices.buy, 0), nullif(prices.buy, 0), nullif(prices.buy, 0),
+                                       nullif(prices.sell, 0), nullif(prices.sell, 0), nullif(prices.sell, 0), nullif(prices.sell, 0),
+                                       $1::bigint, $2::boolean
+                                FROM unnest($3::varchar[], $4::integer[], $5::integer[]) AS prices(name, buy, sell)
+                                         CROSS JOIN unnest(array['hour', 'day', 'week']) AS resolutions(resolution)
+                                ON CONFLICT (name, resolution, bucket, odyssey) DO UPDATE SET
+                                    buy_open = coalesce(commodity_candle.buy_open, excluded.buy_open),
+                                    buy_high = greatest(commodity_candle.buy_high, excluded.buy_high),
+                                    buy_low = least(commodity_candle.buy_low, excluded.buy_low),
+                                    buy_close = coalesce(excluded.buy_close, commodity_candle.buy_close),
+                                    sell_open = coalesce(commodity_candle.sell_open, excluded.sell_open),
+                                    sell_high = greatest(commodity_candle.sell_high, excluded.sell_high),
+                                    sell_low = least(commodity_candle.sell_low, excluded.sell_low),
+                                    sell_close = coalesce(excluded.sell_close, commodity_candle.sell_close),
+                                    timestamp = excluded.timestamp;";
+                            client.lock().await.execute(insert, &[&timestamp, &odyssey, &names, &buy_prices, &sell_prices]).await.unwrap();
                         }
                     } else {
                         warn!("Unknown message: {json}");
@@ -1101,4 +1852,368 @@ pub async fn handle_event(json: JsonValue, client: Arc<Mutex<tokio_postgres::Cli
             }
         }
     }
-}
\ No newline at end of file
+}
+/// Parses the day count out of `EstimatedRemainingTime` (e.g. "3 Days").
+fn parse_days(remaining_time: &str) -> Option<i32> {
+    remaining_time.split_whitespace().next()?.parse().ok()
+}
+
+/// Inserts a system only known by name, address and (if present) position, so rows referencing it
+/// aren't dropped by the foreign key. Already known systems are left untouched.
+async fn insert_system_if_missing(client: &Arc<Mutex<tokio_postgres::Client>>, timestamp: i64, name: &str, address: i64, star_pos: &JsonValue, odyssey: bool) {
+    let x = star_pos[0].as_f32();
+    let y = star_pos[1].as_f32();
+    let z = star_pos[2].as_f32();
+
+    //language=postgresql
+    let insert = "INSERT INTO system (timestamp, name, address, x, y, z, odyssey) VALUES ($1, $2, $3, $4, $5, $6, $7) ON CONFLICT DO NOTHING;";
+    client.lock().await.execute(insert, &[&timestamp, &name, &address, &x, &y, &z, &odyssey]).await.unwrap();
+}
+
+/// Recounts known and detailed scanned bodies (stars included) of a system against its expected
+/// body count and stores the result in `system_exploration`.
+async fn refresh_system_exploration(client: &Arc<Mutex<tokio_postgres::Client>>, timestamp: i64, system_address: i64, odyssey: bool) {
+    //language=postgresql
+    let sql = "
+        INSERT INTO system_exploration (timestamp, system_address, body_count, known_bodies, detailed_bodies, all_bodies_found, odyssey)
+        SELECT $1, address, body_count,
+               CAST((SELECT count(*) FROM body WHERE system_address = $2 AND odyssey = $3) +
+                    (SELECT count(*) FROM star WHERE system_address = $2 AND odyssey = $3) AS INTEGER),
+               CAST((SELECT count(*) FROM body WHERE system_address = $2 AND odyssey = $3 AND scan_type IN ('Detailed', 'NavBeaconDetail')) +
+                    (SELECT count(*) FROM star WHERE system_address = $2 AND odyssey = $3 AND scan_type IN ('Detailed', 'NavBeaconDetail')) AS INTEGER),
+               coalesce(all_bodies_found, false),
+               odyssey
+        FROM system WHERE address = $2 AND odyssey = $3
+        ON CONFLICT (system_address, odyssey) DO UPDATE SET
+            timestamp = excluded.timestamp,
+            body_count = excluded.body_count,
+            known_bodies = excluded.known_bodies,
+            detailed_bodies = excluded.detailed_bodies,
+            all_bodies_found = excluded.all_bodies_found;";
+    client.lock().await.execute(sql, &[&timestamp, &system_address, &odyssey]).await.unwrap();
+}
+
+/// Stores an organic species found on a body together with its genus.
+#[allow(clippy::too_many_arguments)]
+async fn insert_body_organic(client: &Arc<Mutex<tokio_postgres::Client>>, timestamp: i64, system_address: i64, body_id: i32, genus: &str, species: &str, variant: Option<&str>, odyssey: bool) {
+    //language=postgresql
+    let genus_insert = "INSERT INTO body_genus (timestamp, system_address, body_id, genus, odyssey) VALUES ($1,$2,$3,$4,$5) ON CONFLICT DO NOTHING";
+    //language=postgresql
+    let organic_insert = "
+        INSERT INTO body_organic (timestamp, system_address, body_id, genus, species, variant, odyssey) VALUES ($1,$2,$3,$4,$5,$6,$7)
+        ON CONFLICT (system_address, body_id, species, odyssey) DO UPDATE SET
+            timestamp = excluded.timestamp,
+            genus = excluded.genus,
+            variant = coalesce(excluded.variant, body_organic.variant);";
+
+    match client.lock().await.execute(genus_insert, &[&timestamp, &system_address, &body_id, &genus, &odyssey]).await {
+        Ok(_) => {}
+        Err(err) => {
+            if !err.to_string().contains("violates foreign key constraint") {
+                panic!("{}", err);
+            }
+        }
+    }
+    match client.lock().await.execute(organic_insert, &[&timestamp, &system_address, &body_id, &genus, &species, &variant, &odyssey]).await {
+        Ok(_) => {}
+        Err(err) => {
+            if !err.to_string().contains("violates foreign key constraint") {
+                panic!("{}", err);
+            }
+        }
+    }
+}
+
+/// Derives genus and species symbols from a codex variant symbol,
+/// e.g. `$Codex_Ent_Bacterial_12_A_Name;` -> (`$Codex_Ent_Bacterial_Genus_Name;`, `$Codex_Ent_Bacterial_12_Name;`).
+/// The variant suffix is a star class (`A`, `Ae`, `TTS`) or a material (`Tellurium`).
+fn organic_names(variant: &str) -> Option<(String, String)> {
+    let mut parts: Vec<&str> = variant.strip_prefix("$Codex_Ent_")?.strip_suffix("_Name;")?.split('_').collect();
+    let colour = parts.pop()?;
+    if colour.is_empty() || !colour.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return None;
+    }
+    let species = format!("$Codex_Ent_{}_Name;", parts.join("_"));
+    let number = parts.pop()?;
+    if parts.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let genus = format!("$Codex_Ent_{}_Genus_Name;", parts.join("_"));
+    Some((genus, species))
+}
+
+/// Upserts a station with the details found in Docked-like events (type, faction, government, economies,
+/// services, landing pads, body). Details missing from the message keep their stored value.
+async fn upsert_station_details(client: &Arc<Mutex<tokio_postgres::Client>>, timestamp: i64, message: &JsonValue, name: &str, odyssey: bool) {
+    let market_id = match message["MarketID"].as_i64() {
+        None => { return; }
+        Some(market_id) => market_id
+    };
+    let system_name = message["StarSystem"].as_str();
+    let system_address = message["SystemAddress"].as_i64();
+    let station_type = message["StationType"].as_str();
+    let faction = message["StationFaction"]["Name"].as_str();
+    let government = message["StationGovernment"].as_str();
+    let allegiance = message["StationAllegiance"].as_str();
+    let economy = message["StationEconomy"].as_str();
+    let dist_from_star_ls = message["DistFromStarLS"].as_f32();
+    let landing_pads_small = message["LandingPads"]["Small"].as_i32();
+    let landing_pads_medium = message["LandingPads"]["Medium"].as_i32();
+    let landing_pads_large = message["LandingPads"]["Large"].as_i32();
+    //ApproachSettlement has "BodyName", Location has "Body" which is the station itself for orbital stations
+    let (body_id, body_name) = match message["BodyType"].as_str() {
+        Some("Station") => (None, None),
+        _ => (message["BodyID"].as_i32(), message["BodyName"].as_str().or(message["Body"].as_str())),
+    };
+
+    if let (Some(system_name), Some(system_address)) = (system_name, system_address) {
+        insert_system_if_missing(client, timestamp, system_name, system_address, &message["StarPos"], odyssey).await;
+    }
+
+    //language=postgresql
+    let sql = "
+        INSERT INTO station (timestamp, name, market_id, system_name, system_address, type, faction, government, allegiance, economy, dist_from_star_ls,
+                             landing_pads_small, landing_pads_medium, landing_pads_large, body_id, body_name, odyssey)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
+        ON CONFLICT (market_id, odyssey) DO UPDATE SET
+            timestamp = excluded.timestamp,
+            name = excluded.name,
+            system_name = coalesce(excluded.system_name, station.system_name),
+            system_address = coalesce(excluded.system_address, station.system_address),
+            type = coalesce(excluded.type, station.type),
+            faction = coalesce(excluded.faction, station.faction),
+            government = coalesce(excluded.government, station.government),
+            allegiance = coalesce(excluded.allegiance, station.allegiance),
+            economy = coalesce(excluded.economy, station.economy),
+            dist_from_star_ls = coalesce(excluded.dist_from_star_ls, station.dist_from_star_ls),
+            landing_pads_small = coalesce(excluded.landing_pads_small, station.landing_pads_small),
+            landing_pads_medium = coalesce(excluded.landing_pads_medium, station.landing_pads_medium),
+            landing_pads_large = coalesce(excluded.landing_pads_large, station.landing_pads_large),
+            body_id = coalesce(excluded.body_id, station.body_id),
+            body_name = coalesce(excluded.body_name, station.body_name);";
+    match client.lock().await.execute(sql, &[
+        &timestamp, &name, &market_id, &system_name, &system_address, &station_type, &faction, &government, &allegiance, &economy, &dist_from_star_ls,
+        &landing_pads_small, &landing_pads_medium, &landing_pads_large, &body_id, &body_name, &odyssey
+    ]).await {
+        Ok(_) => {}
+        Err(err) => {
+            if !err.to_string().contains("violates foreign key constraint") {
+                panic!("{}", err);
+            }
+        }
+    }
+
+    if !message["StationServices"].is_empty() {
+        //language=postgresql
+        let delete = "DELETE FROM station_service WHERE market_id=$1 and odyssey=$2;";
+        client.lock().await.execute(delete, &[&market_id, &odyssey]).await.unwrap();
+    }
+    for i in 0..message["StationServices"].len() {
+        let service = message["StationServices"][i].as_str().unwrap();
+        //language=postgresql
+        let insert = "INSERT INTO station_service (timestamp, market_id, service, odyssey) VALUES ($1,$2,$3,$4) ON CONFLICT DO NOTHING;";
+        match client.lock().await.execute(insert, &[&timestamp, &market_id, &service, &odyssey]).await {
+            Ok(_) => {}
+            Err(err) => {
+                if !err.to_string().contains("violates foreign key constraint") {
+                    panic!("{}", err);
+                }
+            }
+        }
+    }
+
+    if !message["StationEconomies"].is_empty() {
+        //language=postgresql
+        let delete = "DELETE FROM station_economy WHERE market_id=$1 and odyssey=$2;";
+        client.lock().await.execute(delete, &[&market_id, &odyssey]).await.unwrap();
+    }
+    for i in 0..message["StationEconomies"].len() {
+        let economy = message["StationEconomies"][i]["Name"].as_str().unwrap();
+        let proportion = message["StationEconomies"][i]["Proportion"].as_f32();
+        //language=postgresql
+        let insert = "INSERT INTO station_economy (timestamp, market_id, name, proportion, odyssey) VALUES ($1,$2,$3,$4,$5) ON CONFLICT (market_id, name, odyssey) DO UPDATE SET proportion = excluded.proportion;";
+        match client.lock().await.execute(insert, &[&timestamp, &market_id, &economy, &proportion, &odyssey]).await {
+            Ok(_) => {}
+            Err(err) => {
+                if !err.to_string().contains("violates foreign key constraint") {
+                    panic!("{}", err);
+                }
+            }
+        }
+    }
+}
+
+/// Upserts a fleet carrier. Fields passed as `None` keep their stored value.
+#[allow(clippy::too_many_arguments)]
+async fn upsert_fleet_carrier(client: &Arc<Mutex<tokio_postgres::Client>>, timestamp: i64, market_id: i64, callsign: Option<&str>, name: Option<&str>, docking_access: Option<&str>, allow_notorious: Option<bool>, odyssey: bool) {
+    //language=postgresql
+    let sql = "
+        INSERT INTO fleet_carrier (timestamp, market_id, callsign, name, docking_access, allow_notorious, odyssey)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+        ON CONFLICT (market_id, odyssey) DO UPDATE SET
+            timestamp = excluded.timestamp,
+            callsign = coalesce(excluded.callsign, fleet_carrier.callsign),
+            name = coalesce(excluded.name, fleet_carrier.name),
+            docking_access = coalesce(excluded.docking_access, fleet_carrier.docking_access),
+            allow_notorious = coalesce(excluded.allow_notorious, fleet_carrier.allow_notorious);";
+    client.lock().await.execute(sql, &[&timestamp, &market_id, &callsign, &name, &docking_access, &allow_notorious, &odyssey]).await.unwrap();
+}
+
+/// Sets the current system of a fleet carrier and adds it to the location history if the carrier
+/// wasn't known to be there already. A pending jump to that system is considered done.
+async fn record_fleet_carrier_location(client: &Arc<Mutex<tokio_postgres::Client>>, timestamp: i64, market_id: i64, system_address: i64, body_id: Option<i32>, odyssey: bool) {
+    //language=postgresql
+    let history = "
+        INSERT INTO fleet_carrier_location (timestamp, market_id, system_address, body_id, odyssey)
+        SELECT $1::bigint, $2::bigint, $3::bigint, $4::integer, $5::boolean
+        WHERE NOT EXISTS (SELECT 1 FROM fleet_carrier WHERE market_id = $2 AND odyssey = $5 AND system_address = $3)
+        ON CONFLICT (market_id, timestamp, odyssey) DO NOTHING;";
+    client.lock().await.execute(history, &[&timestamp, &market_id, &system_address, &body_id, &odyssey]).await.unwrap();
+
+    //The carrier's signal stays behind in the systems it left
+    //language=postgresql
+    let signals = "
+        DELETE FROM system_signal USING fleet_carrier
+        WHERE fleet_carrier.market_id = $1 AND fleet_carrier.odyssey = $3
+          AND system_signal.odyssey = $3 AND system_signal.system_address <> $2 AND system_signal.is_station
+          AND (system_signal.name = fleet_carrier.callsign OR system_signal.name LIKE '% ' || fleet_carrier.callsign);";
+    client.lock().await.execute(signals, &[&market_id, &system_address, &odyssey]).await.unwrap();
+
+    //language=postgresql
+    let update = "
+        UPDATE fleet_carrier SET
+            timestamp = $1,
+            body_id = CASE WHEN system_address IS DISTINCT FROM $3 THEN $4 ELSE coalesce($4, body_id) END,
+            system_address = $3,
+            jump_departure = CASE WHEN jump_destination_address = $3 THEN NULL ELSE jump_departure END,
+            jump_destination_address = CASE WHEN jump_destination_address = $3 THEN NULL ELSE jump_destination_address END
+        WHERE market_id = $2 and odyssey = $5;";
+    client.lock().await.execute(update, &[&timestamp, &market_id, &system_address, &body_id, &odyssey]).await.unwrap();
+}
+
+/// Splits a fleet carrier signal name like "THE GENERAL MELCHETT X5W-0XL" into name and callsign.
+/// Carriers without a name only show their callsign.
+/// Megaships ("Hercules-class Bulk Cargo Ship DCH-214") end in a callsign-like id as well and are rejected by their class.
+fn carrier_callsign(signal_name: &str) -> Option<(Option<&str>, &str)> {
+    let (name, callsign) = match signal_name.rsplit_once(' ') {
+        None => (None, signal_name),
+        Some((name, callsign)) => (Some(name), callsign),
+    };
+    if name.is_some_and(|name| name.contains("-class ")) {
+        return None;
+    }
+    let is_callsign = callsign.len() == 7
+        && callsign.char_indices().all(|(i, c)| if i == 3 { c == '-' } else { c.is_ascii_alphanumeric() });
+    if is_callsign { Some((name, callsign)) } else { None }
+}
+
+/// Normalizes journal ("$aerogel_name;") and capi ("Aerogel") material names to "aerogel".
+fn fc_material_name(name: &str) -> String {
+    name.trim_start_matches('$').trim_end_matches("_name;").to_lowercase()
+}
+
+/// Compares the new shipyard/outfitting list of a market with the stored one before it gets replaced.
+/// Appearing and disappearing items are logged in `<table>_change`, `<table>_history` keeps when
+/// each item was first and last seen.
+async fn record_market_item_changes(client: &Arc<Mutex<tokio_postgres::Client>>, timestamp: i64, market_id: i64, table: &str, column: &str, items: &[String], odyssey: bool) {
+    let statements = [
+        //removed
+        format!("INSERT INTO {table}_change (timestamp, market_id, {column}, change, odyssey)
+                 SELECT $1, market_id, {column}, 'removed', odyssey FROM {table}
+                 WHERE market_id = $2 AND odyssey = $4 AND {column} <> ALL($3::varchar[])
+                 ON CONFLICT DO NOTHING;"),
+        //added
+        format!("INSERT INTO {table}_change (timestamp, market_id, {column}, change, odyssey)
+                 SELECT $1, $2, items.item, 'added', $4 FROM unnest($3::varchar[]) AS items(item)
+                 WHERE NOT EXISTS (SELECT 1 FROM {table} WHERE market_id = $2 AND odyssey = $4 AND {column} = items.item)
+                 ON CONFLICT DO NOTHING;"),
+        format!("INSERT INTO {table}_history (market_id, {column}, first_seen, last_seen, odyssey)
+                 SELECT $2, items.item, $1, $1, $4 FROM unnest($3::varchar[]) AS items(item)
+                 ON CONFLICT (market_id, {column}, odyssey) DO UPDATE SET last_seen = excluded.last_seen;"),
+    ];
+    for sql in statements {
+        match client.lock().await.execute(sql.as_str(), &[&timestamp, &market_id, &items, &odyssey]).await {
+            Ok(_) => {}
+            Err(err) => {
+                if !err.to_string().contains("violates foreign key constraint") {
+                    panic!("{}", err);
+                }
+            }
+        }
+    }
+}
+
+/// Sets the class of a system's main star ("K", "M_RedGiant", "DA", ...).
+async fn update_star_class(client: &Arc<Mutex<tokio_postgres::Client>>, system_address: i64, star_class: &str, odyssey: bool) {
+    //language=postgresql
+    let update = "UPDATE system SET star_class = $1 WHERE address = $2 and odyssey = $3;";
+    client.lock().await.execute(update, &[&star_class, &system_address, &odyssey]).await.unwrap();
+}
+
+/// Replaces the hotspots of a ring and sets the ring's own body id.
+async fn insert_ring_signals(client: &Arc<Mutex<tokio_postgres::Client>>, timestamp: i64, message: &JsonValue, system_address: i64, ring_id: i32, ring_name: &str, odyssey: bool) {
+    if let Some(system_name) = message["StarSystem"].as_str() {
+        insert_system_if_missing(client, timestamp, system_name, system_address, &message["StarPos"], odyssey).await;
+    }
+
+    //language=postgresql
+    let update = "UPDATE ring SET id = $1 WHERE system_address = $2 AND name = $3 AND odyssey = $4;";
+    client.lock().await.execute(update, &[&ring_id, &system_address, &ring_name, &odyssey]).await.unwrap();
+
+    if !message["Signals"].is_empty() {
+        //language=postgresql
+        let delete = "DELETE FROM ring_signal WHERE system_address=$1 AND ring_name=$2 AND odyssey=$3;";
+        client.lock().await.execute(delete, &[&system_address, &ring_name, &odyssey]).await.unwrap();
+    }
+    for signal in message["Signals"].members() {
+        let signal_type = signal["Type"].as_str().unwrap();
+        let count = signal["Count"].as_i32();
+
+        //language=postgresql
+        let insert = "INSERT INTO ring_signal (timestamp, system_address, ring_name, ring_id, type, count, odyssey) VALUES ($1,$2,$3,$4,$5,$6,$7) ON CONFLICT (system_address, ring_name, type, odyssey) DO UPDATE SET count = excluded.count;";
+        match client.lock().await.execute(insert, &[&timestamp, &system_address, &ring_name, &ring_id, &signal_type, &count, &odyssey]).await {
+            Ok(_) => {}
+            Err(err) => {
+                if !err.to_string().contains("violates foreign key constraint") {
+                    panic!("{}", err);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derives_organic_names() {
+        let bacterium = Some(("$Codex_Ent_Bacterial_Genus_Name;".to_string(), "$Codex_Ent_Bacterial_12_Name;".to_string()));
+        assert_eq!(organic_names("$Codex_Ent_Bacterial_12_A_Name;"), bacterium);
+        assert_eq!(organic_names("$Codex_Ent_Bacterial_12_Tellurium_Name;"), bacterium);
+        assert_eq!(organic_names("$Codex_Ent_Stratum_07_TTS_Name;"),
+                   Some(("$Codex_Ent_Stratum_Genus_Name;".to_string(), "$Codex_Ent_Stratum_07_Name;".to_string())));
+        assert_eq!(organic_names("$Codex_Ent_Cactoid_01_Ae_Name;"),
+                   Some(("$Codex_Ent_Cactoid_Genus_Name;".to_string(), "$Codex_Ent_Cactoid_01_Name;".to_string())));
+    }
+
+    #[test]
+    fn ignores_non_variant_symbols() {
+        assert_eq!(organic_names("$Codex_Ent_Bacterial_12_Name;"), None);
+        assert_eq!(organic_names("$Codex_Ent_Standard_Ammonia_Worlds_Name;"), None);
+        assert_eq!(organic_names("Bacterium Aurasus"), None);
+    }
+
+    #[test]
+    fn splits_carrier_callsign() {
+        assert_eq!(carrier_callsign("THE GENERAL MELCHETT X5W-0XL"), Some((Some("THE GENERAL MELCHETT"), "X5W-0XL")));
+        assert_eq!(carrier_callsign("Q2K-BHB"), Some((None, "Q2K-BHB")));
+    }
+
+    #[test]
+    fn rejects_non_carrier_signals() {
+        assert_eq!(carrier_callsign("Hercules-class Bulk Cargo Ship DCH-214"), None);
+        assert_eq!(carrier_callsign("Jameson Memorial"), None);
+        assert_eq!(carrier_callsign("$USS_HighGradeEmissions;"), None);
+    }
+}
@@ -22,7 +22,9 @@ use tonic::transport::Channel;
 
 pub use self::proto::inx_client as client;
 
+mod catalogue;
 mod event_handler;
+mod scheduler;
 
 pub mod proto {
     #![allow(missing_docs)]
@@ -50,6 +52,21 @@ async fn main() {
     let database = std::env::var("DATABASE_NAME").unwrap_or("edcas".to_string());
     println!("Database: {}", &database);
 
+    let commodity_history_config = scheduler::CommodityHistoryConfig {
+        interval: std::env::var("COMMODITY_HISTORY_INTERVAL").unwrap_or("3600".to_string()).parse().unwrap(),
+        min_stock: std::env::var("COMMODITY_HISTORY_MIN_STOCK").unwrap_or("1000".to_string()).parse().unwrap(),
+        min_demand: std::env::var("COMMODITY_HISTORY_MIN_DEMAND").unwrap_or("1000".to_string()).parse().unwrap(),
+    };
+    if commodity_history_config.interval == 0 {
+        panic!("COMMODITY_HISTORY_INTERVAL has to be greater than 0");
+    }
+    println!("Commodity history interval: {}s", &commodity_history_config.interval);
+    let candle_retention_config = scheduler::CandleRetentionConfig {
+        hour: std::env::var("CANDLE_RETENTION_HOUR").unwrap_or("14".to_string()).parse().unwrap(),
+        day: std::env::var("CANDLE_RETENTION_DAY").unwrap_or("365".to_string()).parse().unwrap(),
+        week: std::env::var("CANDLE_RETENTION_WEEK").unwrap_or("0".to_string()).parse().unwrap(),
+    };
+
     let tag_env = std::env::var("TAGS").unwrap().to_string();
     let tags: Vec<&str> = tag_env.split(",").collect();
     println!("Tags: {:?}", &tags);
@@ -69,12 +86,21 @@ async fn main() {
         }
     });
     println!("Connected");
+    println!("Running update old database");
+    let script = std::fs::read_to_string("updateOldDatabase.sql").unwrap();
+    postgres_client.batch_execute(&script).await.unwrap();
     println!("Running create Tables");
     let script = std::fs::read_to_string("createTables.sql").unwrap();
     postgres_client.batch_execute(&script).await.unwrap();
     let shareable_client = Arc::new(Mutex::new(postgres_client));
+    println!("Filling module and ship catalogue");
+    catalogue::insert_reference(&shareable_client).await;
+    catalogue::insert_missing(&shareable_client).await;
     println!("Done!");
 
+    tokio::spawn(scheduler::run_commodity_history(shareable_client.clone(), commodity_history_config));
+    tokio::spawn(scheduler::run_candle_retention(shareable_client.clone(), candle_retention_config));
+
     println!("Connecting to inx...");
 
     let inx_url: Uri = {
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use chrono::Utc;
+use tokio::sync::Mutex;
+
+/// Settings of the periodic commodity_history aggregation.
+pub struct CommodityHistoryConfig {
+    /// Seconds between two aggregations
+    pub interval: u64,
+    /// Markets need more stock than this to count for the lowest buy price
+    pub min_stock: i32,
+    /// Markets need more demand than this to count for the highest sell price
+    pub min_demand: i32,
+}
+
+/// Aggregates the galaxy-wide lowest buy price, highest sell price and average mean price of every
+/// commodity into commodity_history once per interval.
+/// If no market passes the stock/demand threshold, all markets are taken into account.
+pub async fn run_commodity_history(client: Arc<Mutex<tokio_postgres::Client>>, config: CommodityHistoryConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.interval));
+    let interval_seconds = config.interval as i64;
+    loop {
+        interval.tick().await;
+        let timestamp = Utc::now().timestamp();
+
+        //Skips the run if the last one is younger than the interval (e.g. after a restart).
+        //A minute of slack keeps a tick that runs a bit earlier within its second from skipping a whole interval.
+        //language=postgresql
+        let sql = "
+            INSERT INTO commodity_history (timestamp, name, buy_price, sell_price, mean_price, odyssey)
+            SELECT $1,
+                   name,
+                   coalesce(min(buy_price) filter (where stock > $2), min(buy_price)),
+                   coalesce(max(sell_price) filter (where demand > $3), max(sell_price)),
+                   CAST(avg(mean_price) AS INTEGER),
+                   odyssey
+            FROM commodity
+            WHERE NOT EXISTS (SELECT 1 FROM commodity_history WHERE timestamp > $1::bigint - $4::bigint + 60)
+            GROUP BY name, odyssey
+            ON CONFLICT (timestamp, name, odyssey) DO NOTHING;";
+        match client.lock().await.execute(sql, &[&timestamp, &config.min_stock, &config.min_demand, &interval_seconds]).await {
+            Ok(rows) => {
+                println!("commodity_history: aggregated {} commodities", rows);
+            }
+            Err(err) => {
+                println!("commodity_history aggregation: {}", err);
+            }
+        }
+    }
+}
+
+/// How long candles of each resolution are kept, in days. 0 keeps them forever.
+pub struct CandleRetentionConfig {
+    pub hour: i64,
+    pub day: i64,
+    pub week: i64,
+}
+
+/// Deletes market and galaxy-wide commodity candles that are older than their resolution's retention.
+/// Runs once per hour.
+pub async fn run_candle_retention(client: Arc<Mutex<tokio_postgres::Client>>, config: CandleRetentionConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(60 * 60));
+    loop {
+        interval.tick().await;
+        let timestamp = Utc::now().timestamp();
+
+        for (resolution, days) in [("hour", config.hour), ("day", config.day), ("week", config.week)] {
+            if days <= 0 {
+                continue;
+            }
+            let oldest = timestamp - days * 24 * 60 * 60;
+            for table in ["market_commodity_candle", "commodity_candle"] {
+                let sql = format!("DELETE FROM {table} WHERE resolution = $1 AND bucket < $2;");
+                match client.lock().await.execute(sql.as_str(), &[&resolution, &oldest]).await {
+                    Ok(_) => {}
+                    Err(err) => {
+                        println!("{table} retention: {}", err);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+-- Brings databases created by older versions of the indexer up to date.
+-- Runs before createTables.sql, so every statement has to be a no-op on an empty database.
+
+alter table if exists system add column if not exists all_bodies_found boolean;
+alter table if exists body add column if not exists scan_type varchar;
+alter table if exists star add column if not exists scan_type varchar;
+alter table if exists system add column if not exists nav_beacon_scanned boolean;
+alter table if exists system add column if not exists star_class varchar;
+alter table if exists body add column if not exists authoritative boolean;
+alter table if exists star add column if not exists authoritative boolean;
+
+alter table if exists station add column if not exists type varchar;
+alter table if exists station add column if not exists faction varchar;
+alter table if exists station add column if not exists government varchar;
+alter table if exists station add column if not exists allegiance varchar;
+alter table if exists station add column if not exists economy varchar;
+alter table if exists station add column if not exists dist_from_star_ls real;
+alter table if exists station add column if not exists landing_pads_small integer;
+alter table if exists station add column if not exists landing_pads_medium integer;
+alter table if exists station add column if not exists landing_pads_large integer;
+alter table if exists station add column if not exists body_id integer;
+alter table if exists station add column if not exists body_name varchar;
+
+-- Stations reference systems by address instead of name
+alter table if exists station add column if not exists system_address bigint;
+alter table if exists station drop constraint if exists station_system_name_odyssey_fkey;
+-- Names are not unique, only the address identifies a system
+alter table if exists system drop constraint if exists system_name_odyssey_key;
+do
+$$
+    begin
+        if to_regclass('station') is not null then
+            -- Names shared by several systems can't be resolved and stay without address
+            update station
+            set system_address = (select min(system.address)
+                                  from system
+                                  where lower(system.name) = lower(station.system_name)
+                                    and system.odyssey = station.odyssey
+                                  having count(*) = 1)
+            where station.system_address is null;
+
+            if not exists (select 1 from pg_constraint where conname = 'station_system_address_odyssey_fkey') then
+                alter table station add foreign key (system_address, odyssey) references system (address, odyssey);
+            end if;
+        end if;
+    end
+$$;
+
+alter table if exists commodity add column if not exists status_flags varchar[];
+
+-- Shipyard and outfitting symbols are stored lowercase
+do
+$$
+    declare
+        item record;
+    begin
+        for item in select *
+                    from (values ('ship', 'ship', ''),
+                                 ('module', 'name', ''),
+                                 ('ship_history', 'ship', ''),
+                                 ('module_history', 'name', ''),
+                                 ('ship_change', 'ship', ' and normalized.timestamp = raw.timestamp'),
+                                 ('module_change', 'name', ' and normalized.timestamp = raw.timestamp')) as items(tbl, col, extra_key)
+            loop
+                if to_regclass(item.tbl) is not null then
+                    execute format('delete from %1$I raw where raw.%2$I <> lower(raw.%2$I) and exists(
+                                        select 1 from %1$I normalized where normalized.market_id = raw.market_id and normalized.odyssey = raw.odyssey
+                                        and normalized.%2$I = lower(raw.%2$I)%3$s)', item.tbl, item.col, item.extra_key);
+                    execute format('update %1$I set %2$I = lower(%2$I) where %2$I <> lower(%2$I)', item.tbl, item.col);
+                end if;
+            end loop;
+    end
+$$;
+
+-- Parents reference the system instead of a body, so stars and barycentres can have parents too
+alter table if exists parent add column if not exists level integer;
+alter table if exists parent drop constraint if exists parent_system_address_body_id_odyssey_fkey;
+do
+$$
+    begin
+        if to_regclass('parent') is not null and
+           not exists (select 1 from pg_constraint where conname = 'parent_system_address_odyssey_fkey') then
+            alter table parent add foreign key (system_address, odyssey) references system (address, odyssey) not valid;
+        end if;
+    end
+$$;
+
+-- Older versions stored the parents of a body without their level. Body ids are handed out from the root down,
+-- so the highest parent id is the direct parent. The links between the parents are derived from the chain as well.
+do
+$$
+    begin
+        if to_regclass('parent') is not null then
+            if exists(select 1 from parent where level is null) then
+                create temporary table parent_chain on commit drop as
+                select system_address,
+                       body_id,
+                       parent_type,
+                       parent_id,
+                       cast(row_number() over (partition by system_address, body_id, odyssey order by parent_id desc) - 1 as integer) as level,
+                       odyssey
+                from parent
+                where level is null;
+
+                update parent
+                set level = chain.level
+                from parent_chain chain
+                where parent.system_address = chain.system_address and parent.body_id = chain.body_id
+                  and parent.parent_id = chain.parent_id and parent.odyssey = chain.odyssey;
+
+                insert into parent (system_address, body_id, parent_type, parent_id, level, odyssey)
+                select child.system_address, child.parent_id, ancestor.parent_type, ancestor.parent_id, ancestor.level - child.level - 1, child.odyssey
+                from parent_chain child
+                         join parent_chain ancestor
+                              on ancestor.system_address = child.system_address and ancestor.body_id = child.body_id
+                                  and ancestor.odyssey = child.odyssey and ancestor.level > child.level
+                on conflict do nothing;
+            end if;
+        end if;
+    end
+$$;
+
+-- Rings are identified by name (a body can have several) and reference the system, so star belts are kept too
+alter table if exists ring drop constraint if exists ring_system_address_body_id_odyssey_fkey;
+alter table if exists ring add column if not exists id integer;
+do
+$$
+    begin
+        if to_regclass('ring') is not null then
+            if not exists (select 1 from pg_indexes where indexname = 'ring_pkey' and indexdef like '%(system_address, name, odyssey)') then
+                alter table ring drop constraint if exists ring_pkey;
+                alter table ring add primary key (system_address, name, odyssey);
+            end if;
+            if not exists (select 1 from pg_constraint where conname = 'ring_system_address_odyssey_fkey') then
+                alter table ring add foreign key (system_address, odyssey) references system (address, odyssey) not valid;
+            end if;
+        end if;
+    end
+$$;